@@ -1,15 +1,40 @@
 use async_trait::async_trait;
 use tokio::sync;
+use tracing::{debug, error, warn};
 
-use crate::{error::Error, state};
+use crate::{chat, error::Error, noalbs, state, switcher};
 
 pub mod obs;
 pub mod obs_v5;
+pub mod vmix;
+
+/// Minimum similarity for `!switch` to pick a scene that doesn't match exactly
+pub const MIN_SCENE_SIMILARITY: f64 = 0.6;
 
 #[async_trait]
 pub trait BroadcastingSoftwareLogic: Send + Sync {
+    /// Switches to the scene with exactly this name
     async fn switch_scene(&self, scene: &str) -> Result<String, Error>;
 
+    /// Switches to the scene using the transition, software without
+    /// transition support switches with the active transition
+    async fn switch_scene_with_transition(
+        &self,
+        scene: &str,
+        _transition: &switcher::Transition,
+    ) -> Result<String, Error> {
+        debug!("Transitions not supported, switching with the active one");
+        self.switch_scene(scene).await
+    }
+
+    /// Sets the preview scene when studio mode is enabled
+    async fn set_preview_scene(&self, _scene: &str) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Names of all the scenes in the current collection
+    async fn scenes(&self) -> Result<Vec<String>, Error>;
+
     async fn start_streaming(&self) -> Result<(), Error>;
 
     async fn stop_streaming(&self) -> Result<(), Error>;
@@ -18,12 +43,51 @@ pub trait BroadcastingSoftwareLogic: Send + Sync {
 
     async fn is_recording(&self) -> Result<bool, Error>;
 
-    async fn fix(&self) -> Result<(), Error>;
+    /// Refreshes the media sources that play a stream, or only the source
+    /// with the name, and returns the names of the refreshed sources
+    async fn fix(&self, source: Option<&str>) -> Result<Vec<String>, Error>;
 
     async fn current_scene(&self) -> Result<String, Error>;
 
     async fn toggle_source(&self, source: &str) -> Result<(String, bool), Error>;
 
+    /// Shows or hides the scene items of the source with exactly this name
+    /// in the current scene and its nested scenes
+    async fn set_scene_item_enabled(&self, _source: &str, _enabled: bool) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_filter_enabled(
+        &self,
+        _source: &str,
+        _filter: &str,
+        _enabled: bool,
+    ) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_input_muted(&self, _input: &str, _muted: bool) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn start_replay_buffer(&self) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn stop_replay_buffer(&self) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Saves the replay buffer and returns the path of the saved file
+    async fn save_replay_buffer(&self) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Changes the text, and the color when given, of a text source
+    async fn set_text(&self, _source: &str, _text: &str, _color: Option<u32>) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn set_collection_and_profile(
         &self,
         source: &crate::config::CollectionPair,
@@ -34,20 +98,170 @@ pub trait BroadcastingSoftwareLogic: Send + Sync {
         state: &sync::RwLockReadGuard<state::State>,
     ) -> Result<state::StreamStatus, Error>;
 
-    async fn get_media_source_status(
-        &self,
-        source_name: &str,
-    ) -> Result<(obws::responses::MediaState, i64), Error>;
+    /// State of the media source and the position of its cursor
+    async fn get_media_source_status(&self, _source_name: &str) -> Result<MediaStatus, Error> {
+        Err(Error::NotSupported)
+    }
 
+    /// Adds a copy of the media source to the scene so its status can be
+    /// read while the scene is shown, returns the name of the copy
     async fn create_special_media_source(
         &self,
-        source_name: &str,
-        scene: &str,
-    ) -> Result<String, Error>;
+        _source_name: &str,
+        _scene: &str,
+    ) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
 
     async fn remove_special_media_source(
         &self,
-        source_name: &str,
-        scene: &str,
-    ) -> Result<(), Error>;
+        _source_name: &str,
+        _scene: &str,
+    ) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Reconnects using the new connection details
+    async fn reconfigure(&self, _connection_info: crate::config::ObsConfig) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaState {
+    None,
+    Playing,
+    Opening,
+    Buffering,
+    Paused,
+    Stopped,
+    Ended,
+    Error,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaStatus {
+    pub state: MediaState,
+
+    /// Position of the media cursor in milliseconds
+    pub cursor: i64,
+}
+
+/// Switches the additional instances that follow the main OBS to the
+/// scene mapped to the scene the main OBS switched to
+pub async fn follow_scene(state: noalbs::UserState, scene: String) {
+    let followers = {
+        let state = state.read().await;
+        let bs = &state.broadcasting_software;
+
+        state
+            .config
+            .additional_software
+            .iter()
+            .filter(|instance| instance.follow_scenes)
+            .filter_map(|instance| {
+                let target = instance.mapped_scene(&scene)?;
+                let connection = bs
+                    .instances
+                    .iter()
+                    .find(|i| i.name.eq_ignore_ascii_case(&instance.name))?;
+
+                Some((
+                    instance.name.to_owned(),
+                    connection.connection.clone(),
+                    target.to_owned(),
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (name, connection, target) in followers {
+        debug!("Switching {} to {}", name, target);
+        if let Err(e) = connection.switch_scene(&target).await {
+            error!("Unable to switch {} to {}: {}", name, target, e);
+        }
+    }
+}
+
+/// Scene with the name closest to the name and how similar it is, from 0 to 1
+pub fn closest_scene<'a>(scenes: &'a [String], name: &str) -> Option<(&'a str, f64)> {
+    let name = name.to_lowercase();
+
+    scenes
+        .iter()
+        .map(|s| {
+            let similarity = strsim::normalized_damerau_levenshtein(&name, &s.to_lowercase());
+            (s.as_str(), similarity)
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+/// Warns in the log and in chat about scenes in the config that don't
+/// exist in the current collection of the broadcasting software
+pub async fn check_scenes(state: &noalbs::UserState, chat_sender: &noalbs::ChatSender) {
+    let state = state.read().await;
+
+    let Some(connection) = &state.broadcasting_software.connection else {
+        return;
+    };
+
+    let scenes = match connection.scenes().await {
+        Ok(scenes) => scenes,
+        Err(e) => {
+            error!("Unable to check the configured scenes: {}", e);
+            return;
+        }
+    };
+
+    let missing = state
+        .configured_scenes()
+        .into_iter()
+        .filter(|s| !scenes.iter().any(|scene| scene == s))
+        .map(|s| s.to_owned())
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return;
+    }
+
+    warn!("Configured scenes not found: {}", missing.join(", "));
+
+    let Some(chat) = &state.config.chat else {
+        return;
+    };
+
+    let message = chat::HandleMessage::InternalChatUpdate(chat::InternalChatUpdate {
+        platform: chat.platform.kind(),
+        channel: chat.username.to_owned(),
+        kind: chat::InternalUpdate::MissingScenes(missing),
+    });
+
+    drop(state);
+    let _ = chat_sender.send(message).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_scene_similarity() {
+        let scenes = [
+            "Live".to_string(),
+            "Low bitrate".to_string(),
+            "BRB".to_string(),
+        ];
+
+        assert_eq!(closest_scene(&scenes, "live"), Some(("Live", 1.0)));
+
+        let (scene, similarity) = closest_scene(&scenes, "low bitrat").unwrap();
+        assert_eq!(scene, "Low bitrate");
+        assert!(similarity >= MIN_SCENE_SIMILARITY);
+
+        let (_, similarity) = closest_scene(&scenes, "starting").unwrap();
+        assert!(similarity < MIN_SCENE_SIMILARITY);
+
+        assert_eq!(closest_scene(&[], "live"), None);
+    }
 }
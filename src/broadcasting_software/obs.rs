@@ -11,14 +11,14 @@ use obws::{
 };
 use serde::Deserialize;
 use tokio::sync::{self, mpsc, Mutex};
-use tracing::{error, info, warn, Instrument};
+use tracing::{debug, error, info, warn, Instrument};
 
 use crate::{
     config, error, noalbs,
     state::{self, ClientStatus},
 };
 
-use super::BroadcastingSoftwareLogic;
+use super::{BroadcastingSoftwareLogic, MediaStatus};
 
 pub struct Obs {
     connection: Arc<Mutex<Option<obws::Client>>>,
@@ -27,7 +27,11 @@ pub struct Obs {
 }
 
 impl Obs {
-    pub fn new(connection_info: config::ObsConfig, state: noalbs::UserState) -> Self {
+    pub fn new(
+        connection_info: config::ObsConfig,
+        state: noalbs::UserState,
+        chat_sender: noalbs::ChatSender,
+    ) -> Self {
         // OBS connection will be held in this arc mutex
         let connection = Arc::new(Mutex::new(None));
 
@@ -45,6 +49,7 @@ impl Obs {
                     state: state_inner,
                     connection: connection_inner,
                     event_sender: event_tx,
+                    chat_sender,
                 };
 
                 // TODO: Any errors to handle?
@@ -73,6 +78,8 @@ impl Obs {
                     scene_name,
                     sources: _,
                 } => {
+                    tokio::spawn(super::follow_scene(state.clone(), scene_name.clone()));
+
                     let mut l = state.write().await;
 
                     let switchable = &l.switcher_state.switchable_scenes;
@@ -244,35 +251,37 @@ async fn get_media_sources_rec(
     }
 }
 
+impl From<MediaState> for super::MediaState {
+    fn from(state: MediaState) -> Self {
+        match state {
+            MediaState::None => Self::None,
+            MediaState::Playing => Self::Playing,
+            MediaState::Opening => Self::Opening,
+            MediaState::Buffering => Self::Buffering,
+            MediaState::Paused => Self::Paused,
+            MediaState::Stopped => Self::Stopped,
+            MediaState::Ended => Self::Ended,
+            MediaState::Error => Self::Error,
+            MediaState::Unknown => Self::Unknown,
+        }
+    }
+}
+
 #[async_trait]
 impl BroadcastingSoftwareLogic for Obs {
     async fn switch_scene(&self, scene: &str) -> Result<String, error::Error> {
-        let scenes = self.get_scenes().await?;
-        let scene = scene.to_lowercase();
-
-        let res = scenes
-            .iter()
-            .enumerate()
-            .map(|(i, s)| {
-                let s = &s.to_lowercase();
-                (i, strsim::normalized_damerau_levenshtein(&scene, s))
-            })
-            .min_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-
-        let scene = if let Some(s) = res {
-            scenes[s.0].to_owned()
-        } else {
-            scene
-        };
-
         let connection = self.connection.lock().await;
 
         let client = connection
             .as_ref()
             .ok_or(error::Error::UnableInitialConnection)?;
 
-        client.scenes().set_current_scene(&scene).await?;
-        Ok(scene)
+        client.scenes().set_current_scene(scene).await?;
+        Ok(scene.to_owned())
+    }
+
+    async fn scenes(&self) -> Result<Vec<String>, error::Error> {
+        self.get_scenes().await
     }
 
     async fn start_streaming(&self) -> Result<(), error::Error> {
@@ -295,8 +304,23 @@ impl BroadcastingSoftwareLogic for Obs {
         Ok(client.streaming().stop_streaming().await?)
     }
 
-    async fn fix(&self) -> Result<(), error::Error> {
-        let media_playing = self.get_media_sources().await?;
+    async fn fix(&self, source: Option<&str>) -> Result<Vec<String>, error::Error> {
+        // A targeted source gets refreshed even when it stopped playing
+        let media_playing = match source {
+            Some(name) => {
+                let source = self
+                    .get_sources()
+                    .await?
+                    .into_iter()
+                    .find(|m| m.source_name.eq_ignore_ascii_case(name))
+                    .ok_or(error::Error::NoSourceFound)?;
+
+                vec![source]
+            }
+            None => self.get_media_sources().await?,
+        };
+
+        let mut fixed = Vec::new();
 
         let connection = self.connection.lock().await;
 
@@ -330,12 +354,17 @@ impl BroadcastingSoftwareLogic for Obs {
                     .iter()
                     .map(|s| s.value.to_lowercase())
                     .collect::<Vec<String>>(),
-                s => unimplemented!("Fix not implemented for {}", s),
+                _ if source.is_some() => return Err(error::Error::NotSupported),
+                s => {
+                    debug!("Fix not supported for {}", s);
+                    continue;
+                }
             };
 
-            if !media_inputs
-                .iter()
-                .any(|m| m.starts_with("rtmp") || m.starts_with("srt"))
+            if source.is_none()
+                && !media_inputs
+                    .iter()
+                    .any(|m| m.starts_with("rtmp") || m.starts_with("srt"))
             {
                 continue;
             }
@@ -361,9 +390,11 @@ impl BroadcastingSoftwareLogic for Obs {
                     render: true,
                 })
                 .await?;
+
+            fixed.push(media.source_name);
         }
 
-        Ok(())
+        Ok(fixed)
     }
 
     async fn toggle_recording(&self) -> Result<(), error::Error> {
@@ -390,7 +421,7 @@ impl BroadcastingSoftwareLogic for Obs {
     async fn get_media_source_status(
         &self,
         source_name: &str,
-    ) -> Result<(MediaState, i64), error::Error> {
+    ) -> Result<MediaStatus, error::Error> {
         let connection = &self.connection.lock().await;
 
         let client = connection
@@ -400,7 +431,10 @@ impl BroadcastingSoftwareLogic for Obs {
         let state = client.media_control().get_media_state(source_name).await?;
         let state_time = client.media_control().get_media_time(source_name).await?;
 
-        Ok((state, state_time.whole_seconds()))
+        Ok(MediaStatus {
+            state: state.into(),
+            cursor: state_time.whole_milliseconds() as i64,
+        })
     }
 
     async fn create_special_media_source(
@@ -601,6 +635,7 @@ pub struct InnerConnection {
     state: noalbs::UserState,
     connection: Arc<Mutex<Option<obws::Client>>>,
     event_sender: mpsc::Sender<obws::events::Event>,
+    chat_sender: noalbs::ChatSender,
 }
 
 impl InnerConnection {
@@ -645,6 +680,8 @@ impl InnerConnection {
                 *connection = Some(client);
             }
 
+            super::check_scenes(&self.state, &self.chat_sender).await;
+
             Self::event_loop(event_stream.unwrap(), self.event_sender.clone()).await;
 
             warn!("Disconnected");
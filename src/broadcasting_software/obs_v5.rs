@@ -6,74 +6,153 @@ use futures_util::StreamExt;
 use obwsv5::{
     events::Event,
     requests::{
+        filters,
         inputs::{self, InputId},
         scene_items::SetEnabled,
         scenes::SceneId,
+        sources::SourceId,
     },
     responses::media_inputs::MediaState,
     Client,
 };
-use tokio::sync::{self, mpsc, Mutex};
-use tracing::{error, info, warn, Instrument};
+use serde::Deserialize;
+use tokio::sync::{self, mpsc, Mutex, Notify};
+use tracing::{debug, error, info, warn, Instrument};
 
 use crate::{
+    chat,
     config::{self, ObsConfig},
-    error, noalbs,
+    error,
+    events::{self, ConnectionState},
+    noalbs,
     state::{self, ClientStatus},
+    switcher,
 };
 
 use super::{
     obs::{FfmpegSource, SourceItem, VlcSource},
-    BroadcastingSoftwareLogic,
+    BroadcastingSoftwareLogic, MediaStatus,
 };
 
+/// Times to check if the replay got saved, 200 ms apart
+const REPLAY_SAVE_ATTEMPTS: u8 = 10;
+
+/// Longest time to wait for a transition of unknown duration to end
+const TRANSITION_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct Obsv5 {
     connection: Arc<Mutex<Option<obwsv5::Client>>>,
+    connection_info: Arc<Mutex<config::ObsConfig>>,
+    reconnect: Arc<Notify>,
     connection_join: tokio::task::JoinHandle<()>,
-    event_join: tokio::task::JoinHandle<()>,
+    event_join: Option<tokio::task::JoinHandle<()>>,
 }
 
-impl Obsv5 {
-    pub fn new(connection_info: config::ObsConfig, state: noalbs::UserState) -> Self {
-        // OBS connection will be held in this arc mutex
-        let connection = Arc::new(Mutex::new(None));
+/// Which OBS a connection controls
+enum Role {
+    /// The OBS the switcher follows, its events update the state of the user
+    Main(noalbs::ChatSender),
 
+    /// An additional OBS with this name
+    Instance(String),
+}
+
+impl Obsv5 {
+    pub fn new(
+        connection_info: config::ObsConfig,
+        state: noalbs::UserState,
+        chat_sender: noalbs::ChatSender,
+    ) -> Self {
         // Will be used to receive events from OBS
         let (event_tx, event_rx) = mpsc::channel(100);
+        let event_join = tokio::spawn(Self::event_handler(
+            event_rx,
+            state.clone(),
+            chat_sender.clone(),
+        ));
+
+        Self::connect(
+            connection_info,
+            state,
+            event_tx,
+            Role::Main(chat_sender),
+            Some(event_join),
+        )
+    }
+
+    /// Connects to an additional OBS that only gets controlled,
+    /// its scenes and stream status don't change the state of the user
+    pub fn new_instance(
+        name: String,
+        connection_info: config::ObsConfig,
+        state: noalbs::UserState,
+    ) -> Self {
+        // Nobody listens to the events of an instance
+        let (event_tx, _) = mpsc::channel(1);
+
+        Self::connect(connection_info, state, event_tx, Role::Instance(name), None)
+    }
+
+    fn connect(
+        connection_info: config::ObsConfig,
+        state: noalbs::UserState,
+        event_sender: mpsc::Sender<Event>,
+        role: Role,
+        event_join: Option<tokio::task::JoinHandle<()>>,
+    ) -> Self {
+        // OBS connection will be held in this arc mutex
+        let connection = Arc::new(Mutex::new(None));
+        let connection_info = Arc::new(Mutex::new(connection_info));
+        let reconnect = Arc::new(Notify::new());
 
         let connection_inner = connection.clone();
-        let state_inner = state.clone();
+        let connection_info_inner = connection_info.clone();
+        let reconnect_inner = reconnect.clone();
         let connection_join = tokio::spawn(async {
-            let user = { state_inner.read().await.config.user.name.to_owned() };
+            let user = { state.read().await.config.user.name.to_owned() };
+            let span = match &role {
+                Role::Main(_) => tracing::info_span!("OBS", %user),
+                Role::Instance(name) => tracing::info_span!("OBS", %user, instance = %name),
+            };
 
             async move {
                 let connection = InnerConnection {
-                    connection_info,
-                    state: state_inner,
+                    connection_info: connection_info_inner,
+                    reconnect: reconnect_inner,
+                    state,
                     connection: connection_inner,
-                    event_sender: event_tx,
+                    event_sender,
+                    role,
                 };
 
                 connection.run().await;
             }
-            .instrument(tracing::info_span!("OBS", %user))
+            .instrument(span)
             .await
         });
 
-        let event_join = tokio::spawn(Self::event_handler(event_rx, state));
-
         Self {
             connection,
+            connection_info,
+            reconnect,
             connection_join,
             event_join,
         }
     }
 
-    async fn event_handler(mut events: mpsc::Receiver<Event>, user_state: noalbs::UserState) {
+    async fn event_handler(
+        mut events: mpsc::Receiver<Event>,
+        user_state: noalbs::UserState,
+        chat_sender: noalbs::ChatSender,
+    ) {
+        let mut audio = switcher::AudioMonitorState::default();
+
         while let Some(event) = events.recv().await {
             match event {
                 Event::CurrentProgramSceneChanged { id } => {
                     let name = id.name;
+                    tokio::spawn(super::follow_scene(user_state.clone(), name.clone()));
+
                     let mut l = user_state.write().await;
 
                     let switchable = &l.switcher_state.switchable_scenes;
@@ -85,6 +164,9 @@ impl Obsv5 {
 
                     l.broadcasting_software.current_scene = name;
                 }
+                Event::StudioModeStateChanged { enabled } => {
+                    user_state.write().await.broadcasting_software.studio_mode = enabled;
+                }
                 Event::StreamStateChanged { active, .. } => {
                     let mut l = user_state.write().await;
 
@@ -118,11 +200,72 @@ impl Obsv5 {
                         l.broadcasting_software.initial_stream_status = None;
                     }
                 }
+                Event::InputVolumeMeters { inputs } => {
+                    let l = user_state.read().await;
+
+                    let Some(monitor) = &l.config.switcher.audio_monitor else {
+                        continue;
+                    };
+
+                    if !l.broadcasting_software.is_streaming {
+                        if audio.reset() {
+                            tokio::spawn(switcher::undo_audio_silence(user_state.clone()));
+                        }
+
+                        continue;
+                    }
+
+                    let peak = inputs
+                        .iter()
+                        .find(|i| i.name == monitor.input)
+                        .map(|input| {
+                            input
+                                .levels
+                                .iter()
+                                .map(|level| level[1])
+                                .fold(0.0, f32::max)
+                        });
+
+                    let silent = audio.update_input(monitor, peak, std::time::Instant::now());
+
+                    if let Some(silent) = silent {
+                        tokio::spawn(switcher::handle_audio_silence(
+                            user_state.clone(),
+                            chat_sender.clone(),
+                            silent,
+                        ));
+                    }
+                }
                 _ => {}
             }
         }
     }
 
+    /// Switches the program scene, through the preview when studio mode is on
+    async fn switch_scene_with_client(
+        &self,
+        client: &Client,
+        scene: &str,
+    ) -> Result<String, error::Error> {
+        let studio_mode = self.connection_info.lock().await.studio_mode;
+
+        if !studio_mode.is_program() && client.ui().studio_mode_enabled().await? {
+            client
+                .scenes()
+                .set_current_preview_scene(SceneId::Name(scene))
+                .await?;
+            client.transitions().trigger().await?;
+
+            return Ok(scene.to_owned());
+        }
+
+        client
+            .scenes()
+            .set_current_program_scene(SceneId::Name(scene))
+            .await?;
+        Ok(scene.to_owned())
+    }
+
     async fn get_scenes(&self) -> Result<Vec<String>, error::Error> {
         let connection = self.connection.lock().await;
 
@@ -172,6 +315,113 @@ impl Obsv5 {
     }
 }
 
+/// Kinds of sources that can play a stream and can be refreshed by `fix`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MediaKind {
+    Ffmpeg,
+    Vlc,
+    MediaPlaylist,
+    Gstreamer,
+    Browser,
+}
+
+impl MediaKind {
+    fn from_kind(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "ffmpeg_source" => Self::Ffmpeg,
+            "vlc_source" => Self::Vlc,
+            "media_playlist_source" => Self::MediaPlaylist,
+            "gstreamer-source" => Self::Gstreamer,
+            "browser_source" => Self::Browser,
+            _ => return None,
+        })
+    }
+
+    /// Reports a media state while playing
+    fn has_media_state(&self) -> bool {
+        matches!(self, Self::Ffmpeg | Self::Vlc | Self::MediaPlaylist)
+    }
+
+    /// Where the source gets its media from, lowercased
+    async fn urls(&self, client: &Client, source: &str) -> Result<Vec<String>, error::Error> {
+        let input = InputId::Name(source);
+
+        let urls = match self {
+            Self::Ffmpeg => client
+                .inputs()
+                .settings::<FfmpegSource>(input)
+                .await?
+                .settings
+                .input
+                .into_iter()
+                .collect(),
+            Self::Vlc | Self::MediaPlaylist => client
+                .inputs()
+                .settings::<VlcSource>(input)
+                .await?
+                .settings
+                .playlist
+                .into_iter()
+                .map(|s| s.value)
+                .collect(),
+            Self::Gstreamer => client
+                .inputs()
+                .settings::<GstreamerSource>(input)
+                .await?
+                .settings
+                .pipeline
+                .split_whitespace()
+                .filter_map(|p| p.split_once('=').map(|(_, v)| v.to_owned()))
+                .collect(),
+            // Too many browser sources are overlays to refresh all of them
+            Self::Browser => Vec::new(),
+        };
+
+        Ok(urls.into_iter().map(|u| u.to_lowercase()).collect())
+    }
+
+    async fn refresh(&self, client: &Client, source: &str) -> Result<(), error::Error> {
+        let input = InputId::Name(source);
+
+        match self {
+            Self::Browser => {
+                client
+                    .inputs()
+                    .press_properties_button(input, "refreshnocache")
+                    .await?
+            }
+            // Applying the settings again restarts the media
+            _ => {
+                client
+                    .inputs()
+                    .set_settings(inputs::SetSettings {
+                        input,
+                        settings: &serde_json::json!({}),
+                        overlay: None,
+                    })
+                    .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings specific to a **GStreamer** source
+#[derive(Deserialize)]
+struct GstreamerSource {
+    #[serde(default)]
+    pipeline: String,
+}
+
+fn is_stream_url(url: &str) -> bool {
+    let url = url.trim_matches('"');
+
+    ["rtmp", "srt", "udp", "rist"]
+        .iter()
+        .any(|protocol| url.starts_with(protocol))
+}
+
 #[async_recursion]
 async fn get_media_sources_rec(
     client: &Client,
@@ -196,8 +446,20 @@ async fn get_media_sources_rec(
     let current_name = scene;
 
     for item in items {
-        if let Some(ref input_kind) = item.input_kind {
-            if matches!(input_kind.as_ref(), "ffmpeg_source" | "vlc_source") {
+        let kind = item.input_kind.as_deref().and_then(MediaKind::from_kind);
+
+        if let (Some(input_kind), Some(kind)) = (&item.input_kind, kind) {
+            if kind == MediaKind::Gstreamer {
+                sources.push(SourceItem {
+                    id: item.id,
+                    scene_name: current_name.to_owned(),
+                    source_name: item.source_name,
+                    source_kind: input_kind.to_owned(),
+                });
+                continue;
+            }
+
+            if kind.has_media_state() {
                 let status = match client
                     .media_inputs()
                     .status(InputId::Name(&item.source_name))
@@ -265,7 +527,7 @@ async fn get_sources_rec(
             id: item.id,
             scene_name: current_name.to_owned(),
             source_name: item.source_name.to_owned(),
-            source_kind: String::new(), // Doesn't matter
+            source_kind: item.input_kind.to_owned().unwrap_or_default(),
         });
 
         if matches!(
@@ -283,27 +545,35 @@ async fn get_sources_rec(
     }
 }
 
+impl From<MediaState> for super::MediaState {
+    fn from(state: MediaState) -> Self {
+        match state {
+            MediaState::None => Self::None,
+            MediaState::Playing => Self::Playing,
+            MediaState::Opening => Self::Opening,
+            MediaState::Buffering => Self::Buffering,
+            MediaState::Paused => Self::Paused,
+            MediaState::Stopped => Self::Stopped,
+            MediaState::Ended => Self::Ended,
+            MediaState::Error => Self::Error,
+            _ => Self::Unknown,
+        }
+    }
+}
+
 #[async_trait]
 impl BroadcastingSoftwareLogic for Obsv5 {
     async fn switch_scene(&self, scene: &str) -> Result<String, error::Error> {
-        let scenes = self.get_scenes().await?;
-        let scene = scene.to_lowercase();
+        let connection = self.connection.lock().await;
 
-        let res = scenes
-            .iter()
-            .enumerate()
-            .map(|(i, s)| {
-                let s = &s.to_lowercase();
-                (i, strsim::normalized_damerau_levenshtein(&scene, s))
-            })
-            .min_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
 
-        let scene = if let Some(s) = res {
-            scenes[s.0].to_owned()
-        } else {
-            scene
-        };
+        self.switch_scene_with_client(client, scene).await
+    }
 
+    async fn set_preview_scene(&self, scene: &str) -> Result<String, error::Error> {
         let connection = self.connection.lock().await;
 
         let client = connection
@@ -312,9 +582,101 @@ impl BroadcastingSoftwareLogic for Obsv5 {
 
         client
             .scenes()
-            .set_current_program_scene(SceneId::Name(&scene))
+            .set_current_preview_scene(SceneId::Name(scene))
             .await?;
-        Ok(scene)
+        Ok(scene.to_owned())
+    }
+
+    async fn switch_scene_with_transition(
+        &self,
+        scene: &str,
+        transition: &switcher::Transition,
+    ) -> Result<String, error::Error> {
+        // A scene transition override can't be removed again through obws,
+        // so the active transition is used for the switch and put back after.
+        // Hold the connection so nothing else switches with the transition
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        let transitions = client.transitions();
+
+        // Still switch when the transition can't be used
+        let previous = match transitions.current().await {
+            Ok(previous) => previous,
+            Err(e) => {
+                warn!("Unable to get the current transition: {}", e);
+                return self.switch_scene_with_client(client, scene).await;
+            }
+        };
+
+        if let Err(e) = transitions.set_current(&transition.name).await {
+            warn!("Unable to set transition {}: {}", transition.name, e);
+            return self.switch_scene_with_client(client, scene).await;
+        }
+
+        if let Some(duration) = transition.duration {
+            let duration = Duration::from_millis(duration).try_into().unwrap();
+
+            if let Err(e) = transitions.set_current_duration(duration).await {
+                warn!(
+                    "Unable to set transition duration {}: {}",
+                    transition.name, e
+                );
+            }
+        }
+
+        // Subscribe before switching so the end of the transition isn't missed
+        let events = client.events();
+
+        let res = self.switch_scene_with_client(client, scene).await;
+
+        // OBS cuts a running transition short when it gets changed
+        if let (Ok(_), Ok(events)) = (&res, events) {
+            let duration = match transition.duration {
+                Some(duration) => Duration::from_millis(duration),
+                None => previous
+                    .duration
+                    .and_then(|d| d.try_into().ok())
+                    .unwrap_or(TRANSITION_TIMEOUT),
+            };
+
+            let mut events = Box::pin(events);
+            let ended = async {
+                while let Some(event) = events.next().await {
+                    if matches!(event, Event::SceneTransitionEnded { .. }) {
+                        break;
+                    }
+                }
+            };
+
+            if tokio::time::timeout(duration + Duration::from_secs(1), ended)
+                .await
+                .is_err()
+            {
+                debug!("Transition {} didn't end in time", transition.name);
+            }
+        }
+
+        // Put back the transition the operator uses
+        if let Err(e) = transitions.set_current(&previous.id.name).await {
+            warn!("Unable to restore transition {}: {}", previous.id.name, e);
+        } else if let (Some(_), Some(duration)) = (transition.duration, previous.duration) {
+            if let Err(e) = transitions.set_current_duration(duration).await {
+                warn!(
+                    "Unable to restore transition duration {}: {}",
+                    previous.id.name, e
+                );
+            }
+        }
+
+        res
+    }
+
+    async fn scenes(&self) -> Result<Vec<String>, error::Error> {
+        self.get_scenes().await
     }
 
     async fn start_streaming(&self) -> Result<(), error::Error> {
@@ -337,8 +699,24 @@ impl BroadcastingSoftwareLogic for Obsv5 {
         Ok(client.streaming().stop().await?)
     }
 
-    async fn fix(&self) -> Result<(), error::Error> {
-        let media_playing = self.get_media_sources().await?;
+    async fn fix(&self, source: Option<&str>) -> Result<Vec<String>, error::Error> {
+        let targets = match source {
+            Some(name) => {
+                let source = self
+                    .get_sources()
+                    .await?
+                    .into_iter()
+                    .find(|s| s.source_name.eq_ignore_ascii_case(name))
+                    .ok_or(error::Error::NoSourceFound)?;
+
+                if MediaKind::from_kind(&source.source_kind).is_none() {
+                    return Err(error::Error::NotSupported);
+                }
+
+                vec![source]
+            }
+            None => self.get_media_sources().await?,
+        };
 
         let connection = self.connection.lock().await;
 
@@ -346,50 +724,37 @@ impl BroadcastingSoftwareLogic for Obsv5 {
             .as_ref()
             .ok_or(error::Error::UnableInitialConnection)?;
 
-        for media in media_playing {
-            let media_inputs = match media.source_kind.as_ref() {
-                "ffmpeg_source" => {
-                    let source = client
-                        .inputs()
-                        .settings::<FfmpegSource>(InputId::Name(&media.source_name))
-                        .await?;
+        let mut fixed = Vec::new();
 
-                    if let Some(input) = source.settings.input {
-                        Vec::from([input.to_lowercase()])
-                    } else {
+        for media in targets {
+            let Some(kind) = MediaKind::from_kind(&media.source_kind) else {
+                debug!("Fix not supported for {}", media.source_kind);
+                continue;
+            };
+
+            // Only refresh every source that plays a stream, unless targeted
+            if source.is_none() {
+                let urls = match kind.urls(client, &media.source_name).await {
+                    Ok(urls) => urls,
+                    Err(e) => {
+                        warn!(
+                            "Unable to read the settings of {}: {}",
+                            media.source_name, e
+                        );
                         continue;
                     }
-                }
-                "vlc_source" => client
-                    .inputs()
-                    .settings::<VlcSource>(InputId::Name(&media.source_name))
-                    .await?
-                    .settings
-                    .playlist
-                    .iter()
-                    .map(|s| s.value.to_lowercase())
-                    .collect::<Vec<String>>(),
-                s => unimplemented!("Fix not implemented for {}", s),
-            };
+                };
 
-            if !media_inputs
-                .iter()
-                .any(|m| m.starts_with("rtmp") || m.starts_with("srt") || m.starts_with("udp"))
-            {
-                continue;
+                if !urls.iter().any(|url| is_stream_url(url)) {
+                    continue;
+                }
             }
 
-            client
-                .inputs()
-                .set_settings(inputs::SetSettings {
-                    input: InputId::Name(&media.source_name),
-                    settings: &serde_json::json!({}),
-                    overlay: None,
-                })
-                .await?;
+            kind.refresh(client, &media.source_name).await?;
+            fixed.push(media.source_name);
         }
 
-        Ok(())
+        Ok(fixed)
     }
 
     async fn toggle_recording(&self) -> Result<(), error::Error> {
@@ -417,25 +782,24 @@ impl BroadcastingSoftwareLogic for Obsv5 {
 
     async fn get_media_source_status(
         &self,
-        _source_name: &str,
-    ) -> Result<(obws::responses::MediaState, i64), error::Error> {
-        Err(error::Error::UnableInitialConnection)
-    }
+        source_name: &str,
+    ) -> Result<MediaStatus, error::Error> {
+        let connection = self.connection.lock().await;
 
-    async fn create_special_media_source(
-        &self,
-        _source_name: &str,
-        _scene_name: &str,
-    ) -> Result<String, error::Error> {
-        Err(error::Error::UnableInitialConnection)
-    }
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
 
-    async fn remove_special_media_source(
-        &self,
-        _source_name: &str,
-        _scene: &str,
-    ) -> Result<(), error::Error> {
-        Err(error::Error::UnableInitialConnection)
+        let status = client
+            .media_inputs()
+            .status(InputId::Name(source_name))
+            .await
+            .map_err(error::Error::from)?;
+
+        Ok(MediaStatus {
+            state: status.state.into(),
+            cursor: status.cursor.map_or(0, |c| c.whole_milliseconds() as i64),
+        })
     }
 
     async fn current_scene(&self) -> Result<String, error::Error> {
@@ -458,17 +822,13 @@ impl BroadcastingSoftwareLogic for Obsv5 {
             .as_ref()
             .ok_or(error::Error::UnableInitialConnection)?;
 
-        let stats = client
-            .general()
-            .stats()
-            .await
-            .map_err(error::Error::ObsV5Error)?;
+        let stats = client.general().stats().await.map_err(error::Error::from)?;
 
         let prev_stream = client
             .streaming()
             .status()
             .await
-            .map_err(error::Error::ObsV5Error)?;
+            .map_err(error::Error::from)?;
 
         tokio::time::sleep(Duration::from_secs(2)).await;
 
@@ -476,7 +836,7 @@ impl BroadcastingSoftwareLogic for Obsv5 {
             .streaming()
             .status()
             .await
-            .map_err(error::Error::ObsV5Error)?;
+            .map_err(error::Error::from)?;
 
         let bytes_delta = (stream.bytes - prev_stream.bytes) as f64 * 8.0;
         let time_delta = stream.duration.as_seconds_f64() - prev_stream.duration.as_seconds_f64();
@@ -492,14 +852,8 @@ impl BroadcastingSoftwareLogic for Obsv5 {
             render_total_frames: stats.render_total_frames as u64,
         };
 
-        if state.broadcasting_software.initial_stream_status.is_some() {
-            ss = ss.calculate_current(
-                state
-                    .broadcasting_software
-                    .initial_stream_status
-                    .as_ref()
-                    .unwrap(),
-            );
+        if let Some(initial) = &state.broadcasting_software.initial_stream_status {
+            ss = ss.calculate_current(initial);
         };
 
         Ok(ss)
@@ -547,6 +901,168 @@ impl BroadcastingSoftwareLogic for Obsv5 {
         Ok((source.source_name.to_owned(), enabled))
     }
 
+    async fn set_scene_item_enabled(
+        &self,
+        source: &str,
+        enabled: bool,
+    ) -> Result<(), error::Error> {
+        let sources = self.get_sources().await?;
+
+        let items = sources
+            .iter()
+            .filter(|s| s.source_name == source)
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            return Err(error::Error::NoSourceFound);
+        }
+
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        for item in items {
+            client
+                .scene_items()
+                .set_enabled(SetEnabled {
+                    scene: SceneId::Name(&item.scene_name),
+                    item_id: item.id,
+                    enabled,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_filter_enabled(
+        &self,
+        source: &str,
+        filter: &str,
+        enabled: bool,
+    ) -> Result<(), error::Error> {
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        client
+            .filters()
+            .set_enabled(filters::SetEnabled {
+                source: SourceId::Name(source),
+                filter,
+                enabled,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_input_muted(&self, input: &str, muted: bool) -> Result<(), error::Error> {
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        client
+            .inputs()
+            .set_muted(InputId::Name(input), muted)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn start_replay_buffer(&self) -> Result<(), error::Error> {
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        Ok(client.replay_buffer().start().await?)
+    }
+
+    async fn stop_replay_buffer(&self) -> Result<(), error::Error> {
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        Ok(client.replay_buffer().stop().await?)
+    }
+
+    async fn save_replay_buffer(&self) -> Result<String, error::Error> {
+        let previous = {
+            let connection = self.connection.lock().await;
+
+            let client = connection
+                .as_ref()
+                .ok_or(error::Error::UnableInitialConnection)?;
+
+            let previous = client.replay_buffer().last_replay().await.ok();
+            client.replay_buffer().save().await?;
+
+            previous
+        };
+
+        // OBS writes the file in the background, don't hold the
+        // connection while waiting for it
+        for _ in 0..REPLAY_SAVE_ATTEMPTS {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            let connection = self.connection.lock().await;
+
+            let client = connection
+                .as_ref()
+                .ok_or(error::Error::UnableInitialConnection)?;
+
+            let path = client.replay_buffer().last_replay().await?;
+            if previous.as_ref() != Some(&path) {
+                return Ok(path);
+            }
+        }
+
+        Err(error::Error::ReplayNotSaved)
+    }
+
+    async fn set_text(
+        &self,
+        source: &str,
+        text: &str,
+        color: Option<u32>,
+    ) -> Result<(), error::Error> {
+        let connection = self.connection.lock().await;
+
+        let client = connection
+            .as_ref()
+            .ok_or(error::Error::UnableInitialConnection)?;
+
+        let mut settings = serde_json::json!({ "text": text });
+
+        // GDI+ uses color and FreeType 2 uses the gradient colors
+        if let Some(color) = color {
+            settings["color"] = color.into();
+            settings["color1"] = color.into();
+            settings["color2"] = color.into();
+        }
+
+        client
+            .inputs()
+            .set_settings(inputs::SetSettings {
+                input: InputId::Name(source),
+                settings: &settings,
+                overlay: Some(true),
+            })
+            .await?;
+
+        Ok(())
+    }
+
     async fn set_collection_and_profile(
         &self,
         source: &config::CollectionPair,
@@ -568,100 +1084,162 @@ impl BroadcastingSoftwareLogic for Obsv5 {
 
         Ok(())
     }
+
+    async fn reconfigure(&self, connection_info: config::ObsConfig) -> Result<(), error::Error> {
+        *self.connection_info.lock().await = connection_info;
+        self.reconnect.notify_one();
+
+        Ok(())
+    }
 }
 
 pub struct InnerConnection {
-    connection_info: config::ObsConfig,
+    connection_info: Arc<Mutex<config::ObsConfig>>,
+    reconnect: Arc<Notify>,
     state: noalbs::UserState,
     connection: Arc<Mutex<Option<obwsv5::Client>>>,
     event_sender: mpsc::Sender<Event>,
+    role: Role,
 }
 
 impl InnerConnection {
     async fn run(&self) {
+        // Last connection state that chat knows about
+        let mut announced = None;
+
         loop {
-            let client = self.get_client().await;
+            let client = match self.get_client(&mut announced).await {
+                Some(client) => client,
+                None => continue,
+            };
 
             use obwsv5::requests::EventSubscription;
-            let events = EventSubscription::SCENES | EventSubscription::OUTPUTS;
+            let events = match self.role {
+                Role::Main(_) => {
+                    let mut events = EventSubscription::SCENES
+                        | EventSubscription::OUTPUTS
+                        | EventSubscription::TRANSITIONS
+                        | EventSubscription::UI;
+
+                    // High volume event, only subscribe when it's used
+                    if self
+                        .state
+                        .read()
+                        .await
+                        .config
+                        .switcher
+                        .audio_monitor
+                        .is_some()
+                    {
+                        events |= EventSubscription::INPUT_VOLUME_METERS;
+                    }
+
+                    events
+                }
+                Role::Instance(_) => EventSubscription::NONE,
+            };
             if let Err(e) = client.reidentify(events).await {
                 error!("Error reidentifying: {:?}", e)
             };
 
             let event_stream = client.events();
 
-            {
-                let state = &mut self.state.write().await;
-                let bs = &mut state.broadcasting_software;
-
-                if let Ok(s) = client.scenes().current_program_scene().await {
-                    bs.current_scene = s.id.name;
+            match &self.role {
+                Role::Main(chat_sender) => {
+                    self.update_state(client).await;
+                    super::check_scenes(&self.state, chat_sender).await;
                 }
+                Role::Instance(_) => *self.connection.lock().await = Some(client),
+            }
 
-                if let Ok(s) = client.streaming().status().await {
-                    bs.is_streaming = s.active;
+            if let Err(e) = &event_stream {
+                error!("Error getting event stream: {}", e);
+            }
+
+            tokio::select! {
+                _ = Self::event_loop(event_stream.unwrap(), self.event_sender.clone()) => {
+                    warn!("Disconnected");
+                }
+                _ = self.reconnect.notified() => {
+                    info!("Reconnecting with the new connection details");
                 }
+            }
 
-                bs.status = ClientStatus::Connected;
+            *self.connection.lock().await = None;
 
-                let bs = &state.broadcasting_software;
-                bs.connected_notifier().notify_waiters();
+            if let Role::Main(_) = self.role {
+                let state = &mut self.state.write().await;
+                let bs = &mut state.broadcasting_software;
+                bs.status = ClientStatus::Disconnected;
+                bs.is_streaming = false;
+            }
 
-                if bs.is_streaming {
-                    bs.start_streaming_notifier().notify_waiters();
-                }
+            self.set_connection_state(ConnectionState::Disconnected, &mut announced)
+                .await;
+        }
+    }
 
-                if state
-                    .switcher_state
-                    .switchable_scenes
-                    .contains(&bs.current_scene)
-                {
-                    bs.switch_scene_notifier().notify_waiters();
-                }
+    /// Stores the client and updates the state of the user with the
+    /// current scene and stream status of OBS
+    async fn update_state(&self, client: obwsv5::Client) {
+        {
+            let state = &mut self.state.write().await;
+            let bs = &mut state.broadcasting_software;
+
+            if let Ok(s) = client.scenes().current_program_scene().await {
+                bs.current_scene = s.id.name;
             }
 
-            if let Err(e) = &event_stream {
-                error!("Error getting event stream: {}", e);
+            if let Ok(s) = client.streaming().status().await {
+                bs.is_streaming = s.active;
             }
 
-            {
-                let mut connection = self.connection.lock().await;
-                *connection = Some(client);
+            if let Ok(enabled) = client.ui().studio_mode_enabled().await {
+                bs.studio_mode = enabled;
             }
 
-            {
-                let ss = {
-                    let read = &self.state.read().await;
-                    let bs = &read.broadcasting_software;
-                    let mut status = None;
-
-                    if bs.is_streaming {
-                        if let Some(client) = &bs.connection {
-                            status = client.info(read).await.ok()
-                        }
-                    }
+            bs.status = ClientStatus::Connected;
 
-                    status
-                };
+            let bs = &state.broadcasting_software;
+            bs.connected_notifier().notify_waiters();
 
-                self.state
-                    .write()
-                    .await
-                    .broadcasting_software
-                    .initial_stream_status = ss;
+            if bs.is_streaming {
+                bs.start_streaming_notifier().notify_waiters();
             }
 
-            Self::event_loop(event_stream.unwrap(), self.event_sender.clone()).await;
-
-            warn!("Disconnected");
-
+            if state
+                .switcher_state
+                .switchable_scenes
+                .contains(&bs.current_scene)
             {
-                let state = &mut self.state.write().await;
-                let bs = &mut state.broadcasting_software;
-                bs.status = ClientStatus::Disconnected;
-                bs.is_streaming = false;
+                bs.switch_scene_notifier().notify_waiters();
             }
         }
+
+        {
+            let mut connection = self.connection.lock().await;
+            *connection = Some(client);
+        }
+
+        let ss = {
+            let read = &self.state.read().await;
+            let bs = &read.broadcasting_software;
+            let mut status = None;
+
+            if bs.is_streaming {
+                if let Some(client) = &bs.connection {
+                    status = client.info(read).await.ok()
+                }
+            }
+
+            status
+        };
+
+        self.state
+            .write()
+            .await
+            .broadcasting_software
+            .initial_stream_status = ss;
     }
 
     /// Attempts to connect to OBS
@@ -670,37 +1248,52 @@ impl InnerConnection {
     /// An exponential backoff strategy is used to keep retrying to connect.
     /// This will grow until the 5th retry failure after which the max seconds
     /// will be reached of 32 seconds.
-    async fn get_client(&self) -> obwsv5::Client {
+    ///
+    /// Returns None when the connection details changed while waiting to
+    /// retry, so the caller can start over with the new details.
+    async fn get_client(&self, announced: &mut Option<ConnectionState>) -> Option<obwsv5::Client> {
         let mut retry_grow = 1;
 
         loop {
             info!("Connecting");
+            self.set_connection_state(ConnectionState::Connecting, announced)
+                .await;
 
             let ObsConfig {
                 host,
                 password,
                 port,
                 ..
-            } = &self.connection_info;
+            } = self.connection_info.lock().await.clone();
 
-            match Client::connect(host, *port, password.as_ref()).await {
+            match Client::connect(host, port, password).await {
                 Ok(client) => {
                     info!("Connected");
+                    self.set_connection_state(ConnectionState::Connected, announced)
+                        .await;
 
-                    break client;
+                    return Some(client);
                 }
                 Err(e) => {
                     warn!("Unable to connect due to: {}", e);
 
                     if let obwsv5::Error::Handshake(h) = e {
                         error!("{}", h);
+
+                        let reason = h.to_string();
+                        self.set_connection_state(ConnectionState::AuthFailed(reason), announced)
+                            .await;
                     }
                 }
             };
 
             let wait = 1 << retry_grow;
             info!("trying to connect again in {} seconds", wait);
-            tokio::time::sleep(Duration::from_secs(wait)).await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(wait)) => {}
+                _ = self.reconnect.notified() => return None,
+            }
 
             if retry_grow < 5 {
                 retry_grow += 1;
@@ -708,6 +1301,52 @@ impl InnerConnection {
         }
     }
 
+    /// Sends the connection state to the WS clients, and to chat when it
+    /// changed and the user wants to know about it
+    async fn set_connection_state(
+        &self,
+        connection_state: ConnectionState,
+        announced: &mut Option<ConnectionState>,
+    ) {
+        // Only the main OBS gets reported
+        let chat_sender = match &self.role {
+            Role::Main(chat_sender) => chat_sender,
+            Role::Instance(_) => return,
+        };
+
+        let state = self.state.read().await;
+        state.send_event(events::Event::ObsConnection(&connection_state));
+
+        // Retrying to connect isn't worth a chat message
+        if connection_state == ConnectionState::Connecting
+            || announced.as_ref() == Some(&connection_state)
+        {
+            return;
+        }
+
+        // Connecting for the first time is expected
+        let first_connect = announced.is_none() && connection_state == ConnectionState::Connected;
+        *announced = Some(connection_state.clone());
+
+        if first_connect {
+            return;
+        }
+
+        let message = match &state.config.chat {
+            Some(chat) if chat.announce_obs_connection => {
+                chat::HandleMessage::InternalChatUpdate(chat::InternalChatUpdate {
+                    platform: chat.platform.kind(),
+                    channel: chat.username.to_owned(),
+                    kind: chat::InternalUpdate::ObsConnection(connection_state),
+                })
+            }
+            _ => return,
+        };
+
+        drop(state);
+        let _ = chat_sender.send(message).await;
+    }
+
     /// Sends all received events to the MPSC
     ///
     /// Blocks until the stream gets disconnected.
@@ -728,6 +1367,9 @@ impl Drop for Obsv5 {
     // Abort the spawned tasks
     fn drop(&mut self) {
         self.connection_join.abort();
-        self.event_join.abort();
+
+        if let Some(event_join) = &self.event_join {
+            event_join.abort();
+        }
     }
 }
@@ -0,0 +1,470 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync;
+use tracing::{error, info, trace, warn, Instrument};
+
+use crate::{
+    config, error, noalbs,
+    state::{self, ClientStatus},
+};
+
+use super::BroadcastingSoftwareLogic;
+
+/// How often the state of vMix gets requested
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// vMix controlled through its HTTP API, inputs are used as scenes
+pub struct Vmix {
+    api: Api,
+    poll_join: tokio::task::JoinHandle<()>,
+}
+
+impl Vmix {
+    pub fn new(
+        connection_info: config::VmixConfig,
+        state: noalbs::UserState,
+        chat_sender: noalbs::ChatSender,
+    ) -> Self {
+        let api = Api::new(&connection_info);
+
+        let api_inner = api.clone();
+        let poll_join = tokio::spawn(async {
+            let user = { state.read().await.config.user.name.to_owned() };
+
+            Self::poll(api_inner, state, chat_sender)
+                .instrument(tracing::info_span!("vMix", %user))
+                .await
+        });
+
+        Self { api, poll_join }
+    }
+
+    /// vMix has no events, so keep requesting the state to notice
+    /// input and stream changes
+    async fn poll(api: Api, user_state: noalbs::UserState, chat_sender: noalbs::ChatSender) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            match api.state().await {
+                Ok(vmix) => {
+                    let was_connected = {
+                        let state = user_state.read().await;
+                        state.broadcasting_software.status == ClientStatus::Connected
+                    };
+
+                    Self::update_state(&user_state, vmix).await;
+
+                    if !was_connected {
+                        super::check_scenes(&user_state, &chat_sender).await;
+                    }
+                }
+                Err(e) => {
+                    trace!("Error requesting the state: {}", e);
+
+                    let state = &mut user_state.write().await;
+                    let bs = &mut state.broadcasting_software;
+
+                    if bs.status == ClientStatus::Connected {
+                        warn!("Disconnected");
+                    }
+
+                    bs.status = ClientStatus::Disconnected;
+                    bs.is_streaming = false;
+                }
+            }
+        }
+    }
+
+    async fn update_state(user_state: &noalbs::UserState, vmix: VmixState) {
+        let active = vmix.active_title().map(|s| s.to_owned());
+
+        let mut l = user_state.write().await;
+        let bs = &mut l.broadcasting_software;
+
+        if bs.status != ClientStatus::Connected {
+            info!("Connected");
+            bs.status = ClientStatus::Connected;
+            bs.connected_notifier().notify_waiters();
+        }
+
+        let streaming = vmix.streaming.is_true();
+
+        if streaming && !bs.is_streaming {
+            bs.last_stream_started_at = std::time::Instant::now();
+            bs.start_streaming_notifier().notify_waiters();
+        }
+
+        if !streaming {
+            bs.stream_status = None;
+            bs.initial_stream_status = None;
+        }
+
+        bs.is_streaming = streaming;
+
+        let Some(name) = active else {
+            return;
+        };
+
+        if bs.current_scene == name {
+            return;
+        }
+
+        tokio::spawn(super::follow_scene(user_state.clone(), name.clone()));
+
+        if l.switcher_state.switchable_scenes.contains(&name) {
+            l.broadcasting_software
+                .switch_scene_notifier()
+                .notify_waiters();
+        }
+
+        l.broadcasting_software.current_scene = name;
+    }
+}
+
+#[async_trait]
+impl BroadcastingSoftwareLogic for Vmix {
+    async fn switch_scene(&self, scene: &str) -> Result<String, error::Error> {
+        let vmix = self.api.state().await?;
+
+        let input = vmix
+            .inputs
+            .input
+            .iter()
+            .find(|i| i.title == scene)
+            .ok_or(error::Error::NoSourceFound)?;
+
+        self.api.function("CutDirect", Some(&input.key)).await?;
+
+        Ok(input.title.to_owned())
+    }
+
+    async fn scenes(&self) -> Result<Vec<String>, error::Error> {
+        let vmix = self.api.state().await?;
+
+        Ok(vmix.inputs.input.into_iter().map(|i| i.title).collect())
+    }
+
+    async fn start_streaming(&self) -> Result<(), error::Error> {
+        self.api.function("StartStreaming", None).await
+    }
+
+    async fn stop_streaming(&self) -> Result<(), error::Error> {
+        self.api.function("StopStreaming", None).await
+    }
+
+    async fn fix(&self, _source: Option<&str>) -> Result<Vec<String>, error::Error> {
+        Err(error::Error::NotSupported)
+    }
+
+    async fn toggle_recording(&self) -> Result<(), error::Error> {
+        self.api.function("StartStopRecording", None).await
+    }
+
+    async fn is_recording(&self) -> Result<bool, error::Error> {
+        Ok(self.api.state().await?.recording.is_true())
+    }
+
+    async fn current_scene(&self) -> Result<String, error::Error> {
+        self.api
+            .state()
+            .await?
+            .active_title()
+            .map(|s| s.to_owned())
+            .ok_or(error::Error::NoSourceFound)
+    }
+
+    /// The API has no output statistics
+    async fn info(
+        &self,
+        _state: &sync::RwLockReadGuard<state::State>,
+    ) -> Result<state::StreamStatus, error::Error> {
+        Err(error::Error::NotSupported)
+    }
+
+    async fn toggle_source(&self, _source: &str) -> Result<(String, bool), error::Error> {
+        Err(error::Error::NotSupported)
+    }
+
+    async fn set_collection_and_profile(
+        &self,
+        _source: &config::CollectionPair,
+    ) -> Result<(), error::Error> {
+        Err(error::Error::NotSupported)
+    }
+}
+
+impl Drop for Vmix {
+    // Abort the spawned task
+    fn drop(&mut self) {
+        self.poll_join.abort();
+    }
+}
+
+/// Client for the vMix HTTP API
+#[derive(Clone)]
+struct Api {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Api {
+    fn new(connection_info: &config::VmixConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        Self {
+            client,
+            url: format!(
+                "http://{}:{}/api/",
+                connection_info.host, connection_info.port
+            ),
+        }
+    }
+
+    /// Current state of vMix
+    async fn state(&self) -> Result<VmixState, error::Error> {
+        let res = self.client.get(&self.url).send().await?;
+
+        if !res.status().is_success() {
+            return Err(error::Error::UnableInitialConnection);
+        }
+
+        let text = res.text().await?;
+        Ok(quick_xml::de::from_str(&text)?)
+    }
+
+    /// Runs a function, optionally on an input
+    async fn function(&self, function: &str, input: Option<&str>) -> Result<(), error::Error> {
+        let mut query = vec![("Function", function)];
+
+        if let Some(input) = input {
+            query.push(("Input", input));
+        }
+
+        let res = self.client.get(&self.url).query(&query).send().await?;
+
+        if !res.status().is_success() {
+            error!("Function {} failed with {}", function, res.status());
+            return Err(error::Error::UnableInitialConnection);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct VmixState {
+    inputs: Inputs,
+    active: u32,
+    recording: Flag,
+    streaming: Flag,
+}
+
+impl VmixState {
+    /// Title of the input that is live
+    fn active_title(&self) -> Option<&str> {
+        self.inputs
+            .input
+            .iter()
+            .find(|i| i.number == self.active)
+            .map(|i| i.title.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Inputs {
+    #[serde(default)]
+    input: Vec<Input>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Input {
+    key: String,
+    number: u32,
+    title: String,
+}
+
+/// Booleans are written as `True` or `False`
+#[derive(Deserialize, Debug)]
+struct Flag {
+    #[serde(rename = "$value")]
+    value: String,
+}
+
+impl Flag {
+    fn is_true(&self) -> bool {
+        self.value.eq_ignore_ascii_case("true")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use warp::Filter;
+
+    use super::*;
+
+    const STATE: &str = r#"<vmix>
+<version>27.0.0.49</version>
+<edition>HD</edition>
+<inputs>
+<input key="26cae087-b7b6-4e45-9d2a-5b1e4c0bf91c" number="1" type="Colour" title="Starting" shortTitle="Starting" state="Paused" position="0" duration="0" loop="False">Starting</input>
+<input key="bf1f5a9c-3fe7-4a8e-9c4b-0f0f3f7dd2a1" number="2" type="Stream" title="Live" shortTitle="Live" state="Running" position="0" duration="0" loop="False">Live</input>
+<input key="0d1c2b3a-4f5e-6d7c-8b9a-a1b2c3d4e5f6" number="3" type="Image" title="Low bitrate" shortTitle="Low bitrate" state="Paused" position="0" duration="0" loop="False">Low bitrate</input>
+</inputs>
+<overlays>
+<overlay number="1" />
+</overlays>
+<preview>1</preview>
+<active>{active}</active>
+<fadeToBlack>False</fadeToBlack>
+<recording duration="0">{recording}</recording>
+<external>False</external>
+<streaming>{streaming}</streaming>
+</vmix>"#;
+
+    /// State of the mock vMix
+    struct Mock {
+        active: u32,
+        recording: bool,
+        streaming: bool,
+        calls: Vec<String>,
+    }
+
+    impl Mock {
+        fn xml(&self) -> String {
+            let flag = |b: bool| if b { "True" } else { "False" };
+
+            STATE
+                .replace("{active}", &self.active.to_string())
+                .replace("{recording}", flag(self.recording))
+                .replace("{streaming}", flag(self.streaming))
+        }
+
+        fn call(&mut self, function: &str, input: Option<&str>) {
+            match (function, input) {
+                ("CutDirect", Some(key)) => {
+                    let number = STATE.split("<input ").skip(1).position(|i| i.contains(key));
+
+                    if let Some(number) = number {
+                        self.active = number as u32 + 1;
+                    }
+                }
+                ("StartStreaming", _) => self.streaming = true,
+                ("StopStreaming", _) => self.streaming = false,
+                ("StartStopRecording", _) => self.recording = !self.recording,
+                _ => {}
+            }
+
+            self.calls.push(function.to_owned());
+        }
+    }
+
+    /// Starts a local server that answers like the vMix API
+    async fn mock_vmix() -> (config::VmixConfig, Arc<Mutex<Mock>>) {
+        let mock = Arc::new(Mutex::new(Mock {
+            active: 1,
+            recording: false,
+            streaming: false,
+            calls: Vec::new(),
+        }));
+
+        let mock_inner = mock.clone();
+        let api = warp::path!("api")
+            .and(warp::query::<std::collections::HashMap<String, String>>())
+            .map(move |query: std::collections::HashMap<String, String>| {
+                let mut mock = mock_inner.lock().unwrap();
+
+                if let Some(function) = query.get("Function") {
+                    mock.call(function, query.get("Input").map(|s| s.as_str()));
+                }
+
+                mock.xml()
+            });
+
+        let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = config::VmixConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+        };
+
+        (config, mock)
+    }
+
+    #[test]
+    fn parse_state() {
+        let xml = STATE
+            .replace("{active}", "2")
+            .replace("{recording}", "True")
+            .replace("{streaming}", "False");
+
+        let vmix: VmixState = quick_xml::de::from_str(&xml).unwrap();
+
+        assert_eq!(vmix.inputs.input.len(), 3);
+        assert_eq!(vmix.active_title(), Some("Live"));
+        assert!(vmix.recording.is_true());
+        assert!(!vmix.streaming.is_true());
+    }
+
+    #[tokio::test]
+    async fn control_mock_server() {
+        let (config, mock) = mock_vmix().await;
+        let api = Api::new(&config);
+
+        let state = api.state().await.unwrap();
+        assert_eq!(state.active_title(), Some("Starting"));
+
+        api.function("StartStreaming", None).await.unwrap();
+        api.function("StartStopRecording", None).await.unwrap();
+
+        let state = api.state().await.unwrap();
+        assert!(state.streaming.is_true());
+        assert!(state.recording.is_true());
+
+        let key = &state.inputs.input[2].key;
+        api.function("CutDirect", Some(key)).await.unwrap();
+
+        let state = api.state().await.unwrap();
+        assert_eq!(state.active_title(), Some("Low bitrate"));
+
+        assert_eq!(
+            mock.lock().unwrap().calls,
+            ["StartStreaming", "StartStopRecording", "CutDirect"]
+        );
+    }
+
+    #[tokio::test]
+    async fn switch_input_by_exact_title() {
+        let (config, _) = mock_vmix().await;
+
+        let vmix = Vmix {
+            api: Api::new(&config),
+            poll_join: tokio::spawn(async {}),
+        };
+
+        assert_eq!(vmix.switch_scene("Live").await.unwrap(), "Live");
+        assert_eq!(vmix.current_scene().await.unwrap(), "Live");
+
+        assert!(vmix.switch_scene("live").await.is_err());
+        assert_eq!(
+            vmix.scenes().await.unwrap(),
+            ["Starting", "Live", "Low bitrate"]
+        );
+
+        assert_eq!(
+            vmix.switch_scene("Low bitrate").await.unwrap(),
+            "Low bitrate"
+        );
+        assert_eq!(vmix.current_scene().await.unwrap(), "Low bitrate");
+    }
+}
@@ -9,7 +9,9 @@ use tokio::time;
 use tracing::{debug, error, info};
 
 use crate::chat::{self, HandleMessage, OptionalScene, Permission};
-use crate::{config, error, events, switcher, user_manager, Noalbs};
+use crate::{
+    broadcasting_software, config, error, events, stream_servers, switcher, user_manager, Noalbs,
+};
 
 pub struct ChatHandler {
     chat_handler_rx: mpsc::Receiver<super::HandleMessage>,
@@ -59,6 +61,14 @@ impl ChatHandler {
             },
         );
 
+        default.insert(
+            Command::Clip,
+            config::CommandInfo {
+                permission: Some(Permission::Mod),
+                ..Default::default()
+            },
+        );
+
         default.insert(
             Command::Fix,
             config::CommandInfo {
@@ -154,6 +164,26 @@ impl ChatHandler {
                             self.handle_raid(update, target_info).await
                         }
                         InternalUpdate::OfflineTimeout => self.handle_offline_timeout(update).await,
+                        InternalUpdate::UnexpectedFormat(ref issue) => {
+                            let issue = issue.to_owned();
+                            self.handle_unexpected_format(update, issue).await
+                        }
+                        InternalUpdate::ObsConnection(ref connection_state) => {
+                            let connection_state = connection_state.to_owned();
+                            self.handle_obs_connection(update, connection_state).await
+                        }
+                        InternalUpdate::MissingScenes(ref scenes) => {
+                            let scenes = scenes.to_owned();
+                            self.handle_missing_scenes(update, scenes).await
+                        }
+                        InternalUpdate::StuckSource(ref source) => {
+                            let source = source.to_owned();
+                            self.handle_stuck_source(update, source).await
+                        }
+                        InternalUpdate::AudioSilence { ref input, silent } => {
+                            let input = input.to_owned();
+                            self.handle_audio_silence(update, input, silent).await
+                        }
                     };
                 }
                 HandleMessage::AutomaticSwitchingScene(ss) => {
@@ -396,6 +426,124 @@ impl ChatHandler {
         Some(())
     }
 
+    pub async fn handle_unexpected_format(
+        &self,
+        host: chat::InternalChatUpdate,
+        issue: switcher::FormatIssue,
+    ) -> Option<()> {
+        let sender = self.chat_senders.get(&host.platform)?;
+        let user = self
+            .user_manager
+            .get_user_by_chat_platform(&host.channel, &host.platform)
+            .await?;
+        let lang = &user.chat_language().await.unwrap().to_string();
+
+        let msg = match issue {
+            switcher::FormatIssue::LowResolution { height, min_height } => t!(
+                "format.lowResolution",
+                locale = lang,
+                height = &height.to_string(),
+                min = &min_height.to_string()
+            ),
+            switcher::FormatIssue::NoAudio => t!("format.noAudio", locale = lang),
+        };
+
+        sender.send_message(host.channel, msg).await;
+
+        Some(())
+    }
+
+    pub async fn handle_stuck_source(
+        &self,
+        host: chat::InternalChatUpdate,
+        source: String,
+    ) -> Option<()> {
+        let sender = self.chat_senders.get(&host.platform)?;
+        let user = self
+            .user_manager
+            .get_user_by_chat_platform(&host.channel, &host.platform)
+            .await?;
+        let lang = &user.chat_language().await.unwrap().to_string();
+
+        let msg = t!("fix.stuck", locale = lang, source = &source);
+        sender.send_message(host.channel, msg).await;
+
+        Some(())
+    }
+
+    pub async fn handle_audio_silence(
+        &self,
+        host: chat::InternalChatUpdate,
+        input: String,
+        silent: bool,
+    ) -> Option<()> {
+        let sender = self.chat_senders.get(&host.platform)?;
+        let user = self
+            .user_manager
+            .get_user_by_chat_platform(&host.channel, &host.platform)
+            .await?;
+        let lang = &user.chat_language().await.unwrap().to_string();
+
+        let msg = if silent {
+            t!("audio.silent", locale = lang, input = &input)
+        } else {
+            t!("audio.recovered", locale = lang, input = &input)
+        };
+
+        sender.send_message(host.channel, msg).await;
+
+        Some(())
+    }
+
+    pub async fn handle_obs_connection(
+        &self,
+        host: chat::InternalChatUpdate,
+        connection_state: events::ConnectionState,
+    ) -> Option<()> {
+        let sender = self.chat_senders.get(&host.platform)?;
+        let user = self
+            .user_manager
+            .get_user_by_chat_platform(&host.channel, &host.platform)
+            .await?;
+        let lang = &user.chat_language().await.unwrap().to_string();
+
+        let msg = match connection_state {
+            events::ConnectionState::Connected => t!("obs.connected", locale = lang),
+            events::ConnectionState::AuthFailed(reason) => {
+                t!("obs.authFailed", locale = lang, reason = &reason)
+            }
+            events::ConnectionState::Disconnected => t!("obs.disconnected", locale = lang),
+            events::ConnectionState::Connecting => return None,
+        };
+
+        sender.send_message(host.channel, msg).await;
+
+        Some(())
+    }
+
+    pub async fn handle_missing_scenes(
+        &self,
+        host: chat::InternalChatUpdate,
+        scenes: Vec<String>,
+    ) -> Option<()> {
+        let sender = self.chat_senders.get(&host.platform)?;
+        let user = self
+            .user_manager
+            .get_user_by_chat_platform(&host.channel, &host.platform)
+            .await?;
+        let lang = &user.chat_language().await.unwrap().to_string();
+
+        let msg = t!(
+            "switch.missingScenes",
+            locale = lang,
+            scenes = &scenes.join(", ")
+        );
+
+        sender.send_message(host.channel, msg).await;
+
+        Some(())
+    }
+
     // TODO: Maybe remove when timeout passed
     pub async fn handle_timeout(&mut self, platform: &chat::ChatPlatform, channel: &str) -> bool {
         let platform_timeouts = self.timeouts.get_mut(platform).unwrap();
@@ -522,13 +670,15 @@ impl DispatchCommand {
             chat::Command::Alias => self.alias(params).await,
             chat::Command::Autostop => self.autostop(params.next()).await,
             chat::Command::Bitrate => self.bitrate().await,
-            chat::Command::Fix => self.fix().await,
+            chat::Command::Clip => self.clip(params.next()).await,
+            chat::Command::Fix => self.fix(params.next()).await,
             chat::Command::Refresh => self.refresh().await,
             chat::Command::Noalbs => self.noalbs(params.next(), params).await,
             chat::Command::Notify => self.notify(params.next()).await,
-            chat::Command::Rec => self.record().await,
-            chat::Command::Start => self.start().await,
-            chat::Command::Stop => self.stop(None).await,
+            chat::Command::Obs => self.obs(params.next(), params.next()).await,
+            chat::Command::Rec => self.record(params.next()).await,
+            chat::Command::Start => self.start(params.next()).await,
+            chat::Command::Stop => self.stop(None, params.next()).await,
             chat::Command::Switch => self.switch(params.next()).await,
             chat::Command::Trigger => {
                 self.trigger(switcher::TriggerType::Low, params.next())
@@ -560,6 +710,7 @@ impl DispatchCommand {
                     .await
             }
             chat::Command::LiveScene => self.live_scene().await,
+            chat::Command::Server => self.server(params.next(), params).await,
             chat::Command::ServerInfo => self.server_info().await,
             chat::Command::Mod => self.enable_mod(params.next()).await,
             chat::Command::Public => self.enable_public(params.next()).await,
@@ -657,18 +808,61 @@ impl DispatchCommand {
             }
         };
 
-        let msg = match self.switch_scene(name).await {
+        let scenes = match self.scenes().await {
+            Ok(scenes) => scenes,
+            Err(e) => {
+                error!("{}", e);
+                self.send(t!("switch.error", locale = &self.lang, scene = name))
+                    .await;
+                return;
+            }
+        };
+
+        let scene = match broadcasting_software::closest_scene(&scenes, name) {
+            Some((scene, similarity))
+                if similarity >= broadcasting_software::MIN_SCENE_SIMILARITY =>
+            {
+                scene
+            }
+            Some((scene, _)) => {
+                let msg = t!(
+                    "switch.didYouMean",
+                    locale = &self.lang,
+                    scene = name,
+                    suggestion = scene
+                );
+                self.send(msg).await;
+                return;
+            }
+            None => {
+                self.send(t!("switch.error", locale = &self.lang, scene = name))
+                    .await;
+                return;
+            }
+        };
+
+        self.switch_exact(scene).await;
+    }
+
+    /// Switches to a scene from the config, the name has to match exactly
+    async fn switch_exact(&self, scene: &str) {
+        let msg = match self.switch_scene(scene).await {
             Ok(scene) => t!("switch.success", locale = &self.lang, scene = &scene),
             Err(e) => {
                 error!("{}", e);
-                t!("switch.error", locale = &self.lang, scene = name)
+                t!("switch.error", locale = &self.lang, scene = scene)
             }
         };
 
         self.send(msg).await;
     }
 
-    async fn start(&self) {
+    async fn start(&self, instance: Option<&str>) {
+        if instance.is_some() {
+            self.start_normal(instance).await;
+            return;
+        }
+
         let (twitch_transcoding, record, starting) = {
             let state = self.user.state.read().await;
             let options = &state.config.optional_options;
@@ -683,7 +877,7 @@ impl DispatchCommand {
             if self.chat_message.platform == chat::ChatPlatform::Twitch && twitch_transcoding {
                 self.start_twitch_transcoding().await
             } else {
-                self.start_normal().await
+                self.start_normal(None).await
             };
 
         if success && starting {
@@ -691,36 +885,32 @@ impl DispatchCommand {
         }
 
         if success && record {
-            self.record().await;
+            self.record(None).await;
         }
     }
 
-    async fn start_bsc(&self) -> Result<(), error::Error> {
+    async fn start_bsc(&self, instance: Option<&str>) -> Result<(), error::Error> {
         let state = self.user.state.read().await;
 
-        let bsc = state
+        state
             .broadcasting_software
-            .connection
-            .as_ref()
-            .ok_or(error::Error::UnableInitialConnection)?;
-
-        bsc.start_streaming().await
+            .target(instance)?
+            .start_streaming()
+            .await
     }
 
-    async fn stop_bsc(&self) -> Result<(), error::Error> {
+    async fn stop_bsc(&self, instance: Option<&str>) -> Result<(), error::Error> {
         let state = self.user.state.read().await;
 
-        let bsc = state
+        state
             .broadcasting_software
-            .connection
-            .as_ref()
-            .ok_or(error::Error::UnableInitialConnection)?;
-
-        bsc.stop_streaming().await
+            .target(instance)?
+            .stop_streaming()
+            .await
     }
 
-    async fn start_normal(&self) -> bool {
-        let start = self.start_bsc().await;
+    async fn start_normal(&self, instance: Option<&str>) -> bool {
+        let start = self.start_bsc(instance).await;
 
         let msg = match start {
             Ok(_) => t!("start.success", locale = &self.lang),
@@ -748,7 +938,7 @@ impl DispatchCommand {
 
         for i in 0..retry {
             debug!("[{}] Starting stream", i);
-            if let Err(e) = self.start_bsc().await {
+            if let Err(e) = self.start_bsc(None).await {
                 self.send(t!(
                     "start.error",
                     locale = &self.lang,
@@ -776,7 +966,7 @@ impl DispatchCommand {
             }
 
             debug!("[{}] Stopping stream", i);
-            if let Err(e) = self.stop_bsc().await {
+            if let Err(e) = self.stop_bsc(None).await {
                 self.send(t!(
                     "stop.error",
                     locale = &self.lang,
@@ -810,7 +1000,7 @@ impl DispatchCommand {
         true
     }
 
-    async fn stop(&self, raid: Option<chat::RaidedInfo>) {
+    async fn stop(&self, raid: Option<chat::RaidedInfo>, instance: Option<&str>) {
         let record = {
             self.user
                 .state
@@ -821,7 +1011,7 @@ impl DispatchCommand {
                 .record_while_streaming
         };
 
-        let stop = self.stop_bsc().await;
+        let stop = self.stop_bsc(instance).await;
 
         let success_msg = if let Some(info) = raid {
             let url = match info.platform {
@@ -847,8 +1037,8 @@ impl DispatchCommand {
 
         self.send(msg).await;
 
-        if stop.is_ok() && record {
-            self.record().await;
+        if stop.is_ok() && record && instance.is_none() {
+            self.record(None).await;
         }
     }
 
@@ -885,16 +1075,21 @@ impl DispatchCommand {
         };
 
         let msg = match &self.user.update_trigger(kind, value).await {
-            Some(value) => t!(
+            Ok(Some(value)) => t!(
                 "trigger.success",
                 locale = &self.lang,
                 number = &format!("{} {}", value, symbol)
             ),
-            None => t!(
+            Ok(None) => t!(
                 "trigger.successDisabled",
                 locale = &self.lang,
                 number = &value.to_string()
             ),
+            Err(_) => {
+                let msg = t!("trigger.obsSource", locale = &self.lang);
+                self.send(msg).await;
+                return;
+            }
         };
 
         self.save_config().await;
@@ -935,7 +1130,7 @@ impl DispatchCommand {
         self.send(msg).await;
     }
 
-    async fn fix(&self) {
+    async fn fix(&self, source: Option<&str>) {
         let state = self.user.state.read().await;
 
         let bsc = match &state.broadcasting_software.connection {
@@ -945,10 +1140,26 @@ impl DispatchCommand {
 
         self.send(t!("fix.try", locale = &self.lang)).await;
 
-        if bsc.fix().await.is_err() {
-            let msg = t!("fix.error", locale = &self.lang);
-            self.send(msg).await;
+        let msg = match bsc.fix(source).await {
+            Ok(fixed) if fixed.is_empty() => t!("fix.nothing", locale = &self.lang),
+            Ok(fixed) => t!(
+                "fix.success",
+                locale = &self.lang,
+                sources = &fixed.join(", ")
+            ),
+            Err(error::Error::NoSourceFound) => t!(
+                "fix.notFound",
+                locale = &self.lang,
+                source = source.unwrap_or_default()
+            ),
+            Err(e) => {
+                error!("{}", e);
+                t!("fix.error", locale = &self.lang)
+            }
         };
+
+        drop(state);
+        self.send(msg).await;
     }
 
     async fn refresh(&self) {
@@ -958,7 +1169,8 @@ impl DispatchCommand {
             Some(s) => s.to_owned(),
             None => {
                 self.send(t!("refresh.noScene", locale = &self.lang)).await;
-                self.fix().await;
+                drop(state);
+                self.fix(None).await;
                 return;
             }
         };
@@ -983,6 +1195,19 @@ impl DispatchCommand {
         self.send(t!("refresh.success", locale = &self.lang)).await;
     }
 
+    async fn scenes(&self) -> Result<Vec<String>, error::Error> {
+        self.user
+            .state
+            .read()
+            .await
+            .broadcasting_software
+            .connection
+            .as_ref()
+            .ok_or(error::Error::NoSoftwareSet)?
+            .scenes()
+            .await
+    }
+
     async fn switch_scene(&self, scene: &str) -> Result<String, error::Error> {
         self.user
             .state
@@ -997,12 +1222,16 @@ impl DispatchCommand {
     }
 
     // Record is a toggle
-    async fn record(&self) {
+    async fn record(&self, instance: Option<&str>) {
         let state = self.user.state.read().await;
 
-        let bsc = match &state.broadcasting_software.connection {
-            Some(b) => b,
-            None => return,
+        let bsc = match state.broadcasting_software.target(instance) {
+            Ok(b) => b,
+            Err(error::Error::InstanceNotFound) => {
+                self.send(t!("rec.errorStatus", locale = &self.lang)).await;
+                return;
+            }
+            Err(_) => return,
         };
 
         let is_recording = match bsc.is_recording().await {
@@ -1026,6 +1255,38 @@ impl DispatchCommand {
         self.send(t!("rec.started", locale = &self.lang)).await;
     }
 
+    async fn clip(&self, command: Option<&str>) {
+        // Saving waits for OBS to write the file, don't hold the state meanwhile
+        let bsc = match &self
+            .user
+            .state
+            .read()
+            .await
+            .broadcasting_software
+            .connection
+        {
+            Some(b) => b.clone(),
+            None => return,
+        };
+
+        let msg = match command {
+            Some("start") => match bsc.start_replay_buffer().await {
+                Ok(()) => t!("clip.started", locale = &self.lang),
+                Err(e) => t!("clip.error", locale = &self.lang, error = &e.to_string()),
+            },
+            Some("stop") => match bsc.stop_replay_buffer().await {
+                Ok(()) => t!("clip.stopped", locale = &self.lang),
+                Err(e) => t!("clip.error", locale = &self.lang, error = &e.to_string()),
+            },
+            _ => match bsc.save_replay_buffer().await {
+                Ok(path) => t!("clip.saved", locale = &self.lang, path = &path),
+                Err(e) => t!("clip.error", locale = &self.lang, error = &e.to_string()),
+            },
+        };
+
+        self.send(msg).await;
+    }
+
     pub async fn version(&self) {
         let msg = format!("Running NOALBS v{}", crate::VERSION);
         self.send(msg).await;
@@ -1138,18 +1399,20 @@ impl DispatchCommand {
     }
 
     async fn switch_optional_scene(&self, scene_name: chat::OptionalScene) {
-        let state = self.user.state.read().await;
-        let optional_scenes = &state.config.optional_scenes;
-        let scene = match scene_name {
-            OptionalScene::Privacy => &optional_scenes.privacy,
-            OptionalScene::Starting => &optional_scenes.starting,
-            OptionalScene::Ending => &optional_scenes.ending,
+        let scene = {
+            let state = self.user.state.read().await;
+            let optional_scenes = &state.config.optional_scenes;
+            match scene_name {
+                OptionalScene::Privacy => optional_scenes.privacy.clone(),
+                OptionalScene::Starting => optional_scenes.starting.clone(),
+                OptionalScene::Ending => optional_scenes.ending.clone(),
+            }
         };
 
         if let Some(scene) = scene {
             self.send(t!("scene.success", locale = &self.lang, scene = scene_name))
                 .await;
-            self.switch(Some(scene)).await;
+            self.switch_exact(&scene).await;
         } else {
             self.send(t!("scene.error", locale = &self.lang, scene = scene_name))
                 .await;
@@ -1158,12 +1421,14 @@ impl DispatchCommand {
 
     // TODO: Actually switch to the right scene
     async fn live_scene(&self) {
-        let state = self.user.state.read().await;
-        let scene = &state.config.switcher.switching_scenes.normal;
+        let scene = {
+            let state = self.user.state.read().await;
+            state.config.switcher.switching_scenes.normal.to_owned()
+        };
 
         self.send(t!("scene.success", locale = &self.lang, scene = "live"))
             .await;
-        self.switch(Some(scene)).await;
+        self.switch_exact(&scene).await;
     }
 
     async fn source_info<'a, I>(&self, server_name: I)
@@ -1174,13 +1439,14 @@ impl DispatchCommand {
         let state = &self.user.state.read().await;
         let stream_servers = &state.config.switcher.stream_servers;
 
+        let max = self.chat_sender.max_message_length();
         let no_info = t!("sourceinfo.noInfo", locale = &self.lang);
 
         if !name.is_empty() {
             let server = match stream_servers.iter().find(|s| s.name == name) {
                 Some(s) => s,
                 None => {
-                    let msg = t!("sourceinfo.noInfo", locale = &self.lang, name = name);
+                    let msg = t!("sourceinfo.notFound", locale = &self.lang, name = name);
                     self.send(msg).await;
 
                     return;
@@ -1188,31 +1454,35 @@ impl DispatchCommand {
             };
 
             let info = match server.stream_server.source_info().await {
-                Some(i) => i,
-                None => no_info,
+                Some(info) => render_source_info(&name, &info, &self.lang),
+                None => vec![format!("{}: {}", name, no_info)],
             };
-            self.send(format!("{}: {}", name, info)).await;
+
+            for msg in split_message(info, ", ", max) {
+                self.send(msg).await;
+            }
 
             return;
         }
 
-        let mut msg = Vec::new();
+        let mut servers = Vec::new();
 
         for s in stream_servers.iter().filter(|ss| ss.enabled) {
-            let info = s.stream_server.source_info().await;
-
-            if let Some(info) = info {
-                msg.push(format!("{}: {}", s.name, info));
+            if let Some(info) = s.stream_server.source_info().await {
+                let parts = render_source_info(&s.name, &info, &self.lang);
+                servers.extend(split_message(parts, ", ", max));
             }
         }
 
-        if msg.is_empty() {
+        if servers.is_empty() {
             self.send(no_info).await;
 
             return;
         }
 
-        self.send(msg.join(" - ")).await;
+        for msg in split_message(servers, " - ", max) {
+            self.send(msg).await;
+        }
     }
 
     async fn enable_mod(&self, enabled: Option<&str>) {
@@ -1262,6 +1532,156 @@ impl DispatchCommand {
         }
     }
 
+    async fn server<'a, I>(&self, command: Option<&str>, args: I)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let args = args.into_iter().collect::<Vec<_>>();
+
+        let msg = match command {
+            Some("list") => self.server_list().await,
+            Some(c @ ("enable" | "disable")) if !args.is_empty() => {
+                let name = args.join(" ");
+                let enabled = c == "enable";
+
+                match self.user.set_stream_server_enabled(&name, enabled).await {
+                    Ok(()) => {
+                        self.save_config().await;
+                        t!(
+                            "server.toggled",
+                            locale = &self.lang,
+                            name = &name,
+                            condition = &condition_to_text(enabled, &self.lang)
+                        )
+                    }
+                    Err(_) => t!("server.notFound", locale = &self.lang, name = &name),
+                }
+            }
+            Some("priority") if args.len() >= 2 => {
+                let (name, priority) = args.split_at(args.len() - 1);
+                let name = name.join(" ");
+                let priority = priority[0];
+
+                match priority.parse::<i32>() {
+                    Ok(p) => match self.user.set_stream_server_priority(&name, p).await {
+                        Ok(()) => {
+                            self.save_config().await;
+                            t!(
+                                "server.priority",
+                                locale = &self.lang,
+                                name = &name,
+                                priority = priority
+                            )
+                        }
+                        Err(_) => t!("server.notFound", locale = &self.lang, name = &name),
+                    },
+                    Err(_) => t!(
+                        "server.priorityError",
+                        locale = &self.lang,
+                        priority = priority
+                    ),
+                }
+            }
+            _ => t!("server.usage", locale = &self.lang),
+        };
+
+        self.send(msg).await;
+    }
+
+    async fn obs(&self, host: Option<&str>, port: Option<&str>) {
+        let Some((current, connected)) = self.user.obs_connection().await else {
+            self.send(t!("obs.notSupported", locale = &self.lang)).await;
+            return;
+        };
+
+        let (host, port) = match (host, port) {
+            (None, _) => {
+                let key = if connected {
+                    "obs.connectedTo"
+                } else {
+                    "obs.notConnected"
+                };
+
+                let mut msg = t!(
+                    key,
+                    locale = &self.lang,
+                    host = &current.host,
+                    port = &current.port.to_string()
+                );
+
+                let studio_mode = self
+                    .user
+                    .state
+                    .read()
+                    .await
+                    .broadcasting_software
+                    .studio_mode;
+                if connected && studio_mode {
+                    let mode = current.studio_mode.to_string();
+                    msg += &t!("obs.studioMode", locale = &self.lang, mode = &mode);
+                }
+
+                self.send(msg).await;
+
+                return;
+            }
+            (Some("reconnect"), None) => (current.host, current.port),
+            (Some(host), Some(port)) => match port.parse::<u16>() {
+                Ok(port) => (host.to_owned(), port),
+                Err(_) => {
+                    let msg = t!("obs.portError", locale = &self.lang, port = port);
+                    self.send(msg).await;
+
+                    return;
+                }
+            },
+            _ => {
+                self.send(t!("obs.usage", locale = &self.lang)).await;
+
+                return;
+            }
+        };
+
+        let msg = match self.user.set_obs_connection(host.clone(), port, None).await {
+            Ok(()) => {
+                self.save_config().await;
+                t!(
+                    "obs.reconnecting",
+                    locale = &self.lang,
+                    host = &host,
+                    port = &port.to_string()
+                )
+            }
+            Err(error::Error::NotSupported) => t!("obs.notSupported", locale = &self.lang),
+            Err(e) => t!("obs.error", locale = &self.lang, error = &e.to_string()),
+        };
+
+        self.send(msg).await;
+    }
+
+    async fn server_list(&self) -> String {
+        let servers = self.user.get_stream_servers().await;
+
+        if servers.is_empty() {
+            return t!("server.noServers", locale = &self.lang);
+        }
+
+        let servers = servers
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} ({}, {})",
+                    s.name,
+                    s.priority.unwrap_or_default(),
+                    condition_to_text(s.enabled, &self.lang)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        t!("server.list", locale = &self.lang, servers = &servers)
+    }
+
     async fn server_info(&self) {
         let state = self.user.state.read().await;
 
@@ -1366,7 +1786,7 @@ impl DispatchCommand {
     }
 
     async fn stop_on_raid(&self, target_info: chat::RaidedInfo) {
-        self.stop(Some(target_info)).await;
+        self.stop(Some(target_info), None).await;
     }
 
     async fn collection(&self, name: Option<&str>) {
@@ -1380,10 +1800,11 @@ impl DispatchCommand {
         };
 
         let state = self.user.state.read().await;
-        let collections = match &state.config.software {
-            config::SoftwareConnection::ObsOld(o) => &o.collections,
-            config::SoftwareConnection::Obs(o) => &o.collections,
-        };
+        let collections = state
+            .config
+            .software
+            .obs_config()
+            .and_then(|o| o.collections.as_ref());
 
         let collections = match collections {
             Some(c) => c,
@@ -1459,6 +1880,75 @@ fn enabled_to_bool(enabled: &str) -> Result<bool, error::Error> {
     Err(error::Error::EnabledToBoolConversionError)
 }
 
+/// Renders every source info field with its template, the first field is
+/// prefixed with the name of the stream server
+fn render_source_info(name: &str, info: &stream_servers::SourceInfo, lang: &str) -> Vec<String> {
+    let mut parts = info
+        .fields
+        .iter()
+        .map(|field| {
+            let key = format!("sourceinfo.fields.{}", field.kind);
+            let mut text = t!(&key, locale = lang);
+
+            for (name, value) in &field.values {
+                text = text.replace(&format!("%{{{}}}", name), &value.to_string());
+            }
+
+            // Remove the placeholders of values that weren't reported
+            while let Some(start) = text.find("%{") {
+                match text[start..].find('}') {
+                    Some(end) => text.replace_range(start..=start + end, ""),
+                    None => break,
+                }
+            }
+
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+
+    match parts.first_mut() {
+        Some(first) => *first = format!("{}: {}", name, first),
+        None => parts.push(name.to_owned()),
+    }
+
+    parts
+}
+
+/// Joins the parts into as few messages as possible without going over
+/// the max length, a part that doesn't fit on its own gets trimmed
+fn split_message(parts: Vec<String>, separator: &str, max: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+
+    for part in parts {
+        let part = if part.chars().count() > max {
+            let mut trimmed = part.chars().take(max.saturating_sub(1)).collect::<String>();
+            trimmed.push('…');
+            trimmed
+        } else {
+            part
+        };
+
+        let len = current.chars().count() + separator.chars().count() + part.chars().count();
+
+        if current.is_empty() {
+            current = part;
+        } else if len <= max {
+            current += separator;
+            current += &part;
+        } else {
+            messages.push(std::mem::replace(&mut current, part));
+        }
+    }
+
+    if !current.is_empty() {
+        messages.push(current);
+    }
+
+    messages
+}
+
 async fn bitrate_msg(user: &Noalbs, lang: &str) -> String {
     let mut msg = String::new();
 
@@ -1565,3 +2055,36 @@ struct StreamPlaybackAccessToken {
     value: String,
     signature: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_fields() {
+        let info = stream_servers::SourceInfo::default()
+            .with("bitrate", 6000)
+            .with("rtt", 40.5)
+            .with_values("video", [("height", 1080.into()), ("codec", "H264".into())]);
+
+        assert_eq!(
+            render_source_info("belabox", &info, "en"),
+            vec!["belabox: 6000 Kbps", "40.5 ms", "1080p H264"]
+        );
+    }
+
+    #[test]
+    fn split_long_messages() {
+        let parts = vec!["aaaa".to_string(), "bbbb".to_string(), "cccc".to_string()];
+
+        assert_eq!(
+            split_message(parts.clone(), ", ", 100),
+            vec!["aaaa, bbbb, cccc"]
+        );
+        assert_eq!(split_message(parts, ", ", 10), vec!["aaaa, bbbb", "cccc"]);
+        assert_eq!(
+            split_message(vec!["abcdefgh".to_string()], ", ", 5),
+            vec!["abcd…"]
+        );
+    }
+}
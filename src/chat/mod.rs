@@ -4,7 +4,7 @@ use std::fmt::Display;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{error, switcher};
+use crate::{error, events, switcher};
 
 pub mod chat_handler;
 pub mod kick;
@@ -18,6 +18,11 @@ pub use twitch::Twitch;
 pub trait ChatLogic: Send + Sync {
     // TODO: This should return an error
     async fn send_message(&self, channel: String, message: String);
+
+    /// Longest message the platform accepts
+    fn max_message_length(&self) -> usize {
+        500
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -25,10 +30,13 @@ pub enum Command {
     Alias,
     Autostop,
     Bitrate,
+    Clip,
     Fix,
     Mod,
     Noalbs,
     Notify,
+    Obs,
+    Server,
     ServerInfo,
     Otrigger,
     Ortrigger,
@@ -62,10 +70,13 @@ impl From<&str> for Command {
             "alias" => Command::Alias,
             "autostop" => Command::Autostop,
             "bitrate" => Command::Bitrate,
+            "clip" => Command::Clip,
             "fix" => Command::Fix,
             "mod" => Command::Mod,
             "noalbs" => Command::Noalbs,
             "notify" => Command::Notify,
+            "obs" => Command::Obs,
+            "server" => Command::Server,
             "serverinfo" => Command::ServerInfo,
             "otrigger" => Command::Otrigger,
             "ortrigger" => Command::Ortrigger,
@@ -208,6 +219,11 @@ pub struct InternalChatUpdate {
 pub enum InternalUpdate {
     Raided(RaidedInfo),
     OfflineTimeout,
+    UnexpectedFormat(switcher::FormatIssue),
+    ObsConnection(events::ConnectionState),
+    MissingScenes(Vec<String>),
+    StuckSource(String),
+    AudioSilence { input: String, silent: bool },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
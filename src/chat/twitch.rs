@@ -67,13 +67,13 @@ impl Twitch {
                     );
                     pubsub.add_raid(state.channel_id, state.channel_login).await;
                 }
-                message::ServerMessage::Notice(msg) => {
-                    if msg.message_text == "Login authentication failed" {
-                        error!("Twitch authentication failed");
+                message::ServerMessage::Notice(msg)
+                    if msg.message_text == "Login authentication failed" =>
+                {
+                    error!("Twitch authentication failed");
 
-                        // TODO: Handle panic
-                        // panic!("Twitch authentication failed");
-                    }
+                    // TODO: Handle panic
+                    // panic!("Twitch authentication failed");
                 }
                 message::ServerMessage::Privmsg(msg) => {
                     let permission =
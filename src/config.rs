@@ -14,6 +14,11 @@ pub struct Config {
     pub user: User,
     pub switcher: Switcher,
     pub software: SoftwareConnection,
+
+    /// Other OBS instances that get controlled next to the main one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_software: Vec<SoftwareInstance>,
+
     pub chat: Option<Chat>,
     pub optional_scenes: OptionalScenes,
     pub optional_options: OptionalOptions,
@@ -56,9 +61,36 @@ pub struct Switcher {
 
     /// Add multiple stream servers to watch before switching to low or offline
     pub stream_servers: Vec<stream_servers::StreamServer>,
+
+    /// Text source in OBS that shows the bitrate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlay: Option<switcher::BitrateOverlay>,
+
+    /// Media source in OBS that gets refreshed when it's stuck
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<switcher::SourceWatchdog>,
+
+    /// OBS input that gets reported when it's silent while live
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_monitor: Option<switcher::AudioMonitor>,
 }
 
 impl Switcher {
+    /// OBS doesn't report the bitrate of a media source, so the bitrate
+    /// triggers would silently be ignored with an `Obs` stream server
+    pub fn check_bitrate_triggers(&mut self) -> Result<(), error::Error> {
+        let has_obs = self
+            .stream_servers
+            .iter_mut()
+            .any(|s| s.stream_server.as_any_mut().is::<stream_servers::Obs>());
+
+        if has_obs && self.triggers.uses_bitrate() {
+            return Err(error::Error::BitrateTriggersWithObs);
+        }
+
+        Ok(())
+    }
+
     pub fn add_stream_server(&mut self, stream_server: stream_servers::StreamServer) {
         self.stream_servers.push(stream_server);
 
@@ -67,8 +99,7 @@ impl Switcher {
 
     /// Sort by highest number first
     pub fn sort_stream_servers(&mut self) {
-        self.stream_servers
-            .sort_by(|a, b| a.priority.cmp(&b.priority));
+        self.stream_servers.sort_by_key(|s| s.priority);
     }
 
     pub fn set_bitrate_switcher_enabled(&mut self, enabled: bool) {
@@ -89,10 +120,15 @@ impl Default for Switcher {
             auto_switch_notification: true,
             triggers: switcher::Triggers::default(),
             stream_servers: Vec::new(),
+            overlay: None,
+            watchdog: None,
+            audio_monitor: None,
             switching_scenes: switcher::SwitchingScenes {
                 normal: "live".to_string(),
                 low: "low".to_string(),
                 offline: "offline".to_string(),
+                transitions: Default::default(),
+                actions: Default::default(),
             },
             retry_attempts: MAX_LOW_RETRY,
         }
@@ -105,6 +141,24 @@ impl Default for Switcher {
 pub enum SoftwareConnection {
     ObsOld(ObsConfig),
     Obs(ObsConfig),
+    Vmix(VmixConfig),
+}
+
+impl SoftwareConnection {
+    /// Connection details when the software is OBS
+    pub fn obs_config(&self) -> Option<&ObsConfig> {
+        match self {
+            SoftwareConnection::ObsOld(c) | SoftwareConnection::Obs(c) => Some(c),
+            SoftwareConnection::Vmix(_) => None,
+        }
+    }
+
+    pub fn obs_config_mut(&mut self) -> Option<&mut ObsConfig> {
+        match self {
+            SoftwareConnection::ObsOld(c) | SoftwareConnection::Obs(c) => Some(c),
+            SoftwareConnection::Vmix(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -115,6 +169,92 @@ pub struct ObsConfig {
 
     // Configurable profile and collection pairs
     pub collections: Option<HashMap<String, CollectionPair>>,
+
+    /// How scenes get switched while studio mode is enabled in OBS
+    #[serde(default, skip_serializing_if = "StudioMode::is_program")]
+    pub studio_mode: StudioMode,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StudioMode {
+    /// Switch the program scene directly
+    #[default]
+    Program,
+
+    /// Set the preview scene and trigger the transition
+    Transition,
+
+    /// Only set the preview scene for low and offline switches,
+    /// other switches use the transition
+    Preview,
+}
+
+impl StudioMode {
+    pub fn is_program(&self) -> bool {
+        *self == StudioMode::Program
+    }
+}
+
+impl std::fmt::Display for StudioMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match self {
+            StudioMode::Program => "program",
+            StudioMode::Transition => "transition",
+            StudioMode::Preview => "preview",
+        };
+
+        write!(f, "{}", mode)
+    }
+}
+
+/// An additional OBS instance, for example a second PC that records
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SoftwareInstance {
+    /// Name used to target this instance in chat (ex; !start recording )
+    pub name: String,
+
+    pub connection: ObsConfig,
+
+    /// Switch scenes whenever the main OBS switches scenes
+    #[serde(default = "default_follow_scenes")]
+    pub follow_scenes: bool,
+
+    /// Scene of the main OBS mapped to the scene to use on this instance,
+    /// unmapped scenes use the same name and an empty name skips the switch
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub scenes: HashMap<String, String>,
+}
+
+fn default_follow_scenes() -> bool {
+    true
+}
+
+impl SoftwareInstance {
+    /// Scene on this instance for the scene of the main OBS
+    pub fn mapped_scene<'a>(&'a self, scene: &'a str) -> Option<&'a str> {
+        let mapped = self.scenes.get(scene).map_or(scene, |s| s.as_str());
+
+        if mapped.is_empty() {
+            return None;
+        }
+
+        Some(mapped)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VmixConfig {
+    pub host: String,
+
+    /// Port of the web controller
+    #[serde(default = "default_vmix_port")]
+    pub port: u16,
+}
+
+fn default_vmix_port() -> u16 {
+    8088
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -146,6 +286,9 @@ pub struct Chat {
     pub enable_mod_commands: bool,
     pub enable_auto_stop_stream_on_host_or_raid: bool,
     pub announce_raid_on_auto_stop: bool,
+
+    /// Let chat know when the connection to OBS is lost or restored
+    pub announce_obs_connection: bool,
     pub commands: Option<HashMap<chat::Command, CommandInfo>>,
 }
 
@@ -161,6 +304,7 @@ impl Default for Chat {
             enable_mod_commands: true,
             enable_auto_stop_stream_on_host_or_raid: true,
             announce_raid_on_auto_stop: true,
+            announce_obs_connection: false,
             commands: None,
         }
     }
@@ -238,6 +382,7 @@ impl ConfigLogic for File {
         };
 
         config.switcher.sort_stream_servers();
+        config.switcher.check_bitrate_triggers()?;
 
         if let Some(chat) = &mut config.chat {
             chat.username.make_ascii_lowercase();
@@ -294,6 +439,12 @@ pub struct OptionalOptions {
 
     /// Switch from the starting scene to the live scene when the stream starts
     pub switch_from_starting_scene_to_live_scene: bool,
+
+    /// Save the replay buffer when switching to the low scene
+    pub save_replay_buffer_on_low: bool,
+
+    /// Save the replay buffer when switching to the offline scene
+    pub save_replay_buffer_on_offline: bool,
 }
 
 impl Default for OptionalOptions {
@@ -306,6 +457,8 @@ impl Default for OptionalOptions {
             record_while_streaming: false,
             switch_to_starting_scene_on_stream_start: false,
             switch_from_starting_scene_to_live_scene: false,
+            save_replay_buffer_on_low: false,
+            save_replay_buffer_on_offline: false,
         }
     }
 }
@@ -371,6 +524,7 @@ impl From<ConfigOld> for Config {
             password: Some(o.obs.password),
             port: full_host.next().unwrap().parse().unwrap(),
             collections: Some(HashMap::new()),
+            studio_mode: StudioMode::default(),
         });
 
         let mut config = Config {
@@ -387,15 +541,19 @@ impl From<ConfigOld> for Config {
                     rtt: o.obs.high_rtt_trigger,
                     offline: None,
                     rtt_offline: None,
+                    ..Default::default()
                 },
                 switching_scenes: switcher::SwitchingScenes {
                     normal: o.obs.normal_scene,
                     low: o.obs.low_bitrate_scene,
                     offline: o.obs.offline_scene,
+                    transitions: Default::default(),
+                    actions: Default::default(),
                 },
                 ..Default::default()
             },
             software,
+            additional_software: Vec::new(),
             chat: Some(Chat {
                 platform: ConfigChatPlatform::Twitch,
                 username: o.twitch_chat.channel,
@@ -474,21 +632,25 @@ impl From<RtmpOld> for stream_servers::StreamServer {
                 stats_url: r.stats,
                 application: r.application.unwrap(),
                 key: r.key.unwrap(),
-                client: reqwest::Client::new(),
+                client: Default::default(),
+                recent: Default::default(),
             }),
             "node-media-server" => Box::new(stream_servers::nms::NodeMediaServer {
                 stats_url: r.stats,
                 application: r.application.unwrap(),
                 key: r.key.unwrap(),
-                auth: None,
-                client: reqwest::Client::new(),
+                client: Default::default(),
+                recent: Default::default(),
             }),
             "nimble" => Box::new(stream_servers::nimble::Nimble {
                 id: r.id.unwrap(),
                 stats_url: r.stats,
                 application: r.application.unwrap(),
                 key: r.key.unwrap(),
-                client: reqwest::Client::new(),
+                bitrate_source: Default::default(),
+                counter: Default::default(),
+                client: Default::default(),
+                recent: Default::default(),
             }),
             "srt-live-server" => {
                 let stats_url = r.stats;
@@ -499,13 +661,13 @@ impl From<RtmpOld> for stream_servers::StreamServer {
                     Box::new(stream_servers::belabox::Belabox {
                         stats_url,
                         publisher,
-                        client: reqwest::Client::new(),
+                        client: Default::default(),
                     })
                 } else {
                     Box::new(stream_servers::sls::SrtLiveServer {
                         stats_url,
                         publisher,
-                        client: reqwest::Client::new(),
+                        client: Default::default(),
                     })
                 }
             }
@@ -10,10 +10,10 @@ pub enum Error {
     XmlParsing(#[from] quick_xml::DeError),
 
     #[error("OBS error {0}")]
-    ObsError(#[from] obws::Error),
+    ObsError(Box<obws::Error>),
 
     #[error("OBS error {0}")]
-    ObsV5Error(#[from] obwsv5::Error),
+    ObsV5Error(Box<obwsv5::Error>),
 
     #[error("SwitchType conversion not allowed")]
     SwitchTypeNotSupported,
@@ -50,6 +50,39 @@ pub enum Error {
     #[error("Config file error")]
     ConfigFileError(#[source] std::io::Error),
 
+    #[error("Stream server not found")]
+    StreamServerNotFound,
+
+    #[error("Stream server already exists")]
+    StreamServerAlreadyExists,
+
+    #[error("Push id is already used by another user")]
+    PushIdTaken,
+
+    #[error("The low and offline triggers can't be used with an Obs stream server")]
+    BitrateTriggersWithObs,
+
+    #[error("No software instance found with that name")]
+    InstanceNotFound,
+
+    #[error("Replay buffer didn't save a replay")]
+    ReplayNotSaved,
+
     #[error("No server info available")]
     NoServerInfo,
+
+    #[error("Not supported by the broadcasting software")]
+    NotSupported,
+}
+
+impl From<obws::Error> for Error {
+    fn from(e: obws::Error) -> Self {
+        Error::ObsError(Box::new(e))
+    }
+}
+
+impl From<obwsv5::Error> for Error {
+    fn from(e: obwsv5::Error) -> Self {
+        Error::ObsV5Error(Box::new(e))
+    }
 }
@@ -7,6 +7,19 @@ use serde::Serialize;
 pub enum Event<'a> {
     PrefixChanged { prefix: &'a str },
     SceneSwitched { scene: &'a str },
+    ObsConnection(&'a ConnectionState),
+    AudioSilence { input: &'a str, silent: bool },
+}
+
+/// State of the connection to the broadcasting software
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "state", content = "reason")]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    AuthFailed(String),
+    Disconnected,
 }
 
 #[cfg(test)]
@@ -23,4 +36,20 @@ mod tests {
         let expected = r#"{"event":"prefixChanged","data":{"prefix":"!"}}"#;
         assert_eq!(expected, json);
     }
+
+    #[test]
+    fn connection_event() {
+        let state = ConnectionState::AuthFailed("Authentication failed.".to_string());
+        let json = serde_json::to_string(&Event::ObsConnection(&state)).unwrap();
+
+        let expected = r#"{"event":"obsConnection","data":{"state":"authFailed","reason":"Authentication failed."}}"#;
+        assert_eq!(expected, json);
+
+        let json =
+            serde_json::to_string(&Event::ObsConnection(&ConnectionState::Connected)).unwrap();
+        assert_eq!(
+            r#"{"event":"obsConnection","data":{"state":"connected"}}"#,
+            json
+        );
+    }
 }
@@ -1,10 +1,10 @@
 use std::{collections::HashMap, sync::Arc};
 
 use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, info};
+use tracing::{error, info};
 
 use crate::{
-    broadcasting_software::{obs::Obs, obs_v5::Obsv5, BroadcastingSoftwareLogic},
+    broadcasting_software::{obs::Obs, obs_v5::Obsv5, vmix::Vmix, BroadcastingSoftwareLogic},
     chat, config, error,
     state::{self, State},
     stream_servers,
@@ -56,37 +56,48 @@ impl Noalbs {
         {
             let mut w_state = state.write().await;
 
-            let connection: Box<dyn BroadcastingSoftwareLogic> = match w_state.config.software {
+            let connection: Arc<dyn BroadcastingSoftwareLogic> = match w_state.config.software {
                 config::SoftwareConnection::ObsOld(ref obs_conf) => {
-                    let obs = Obs::new(obs_conf.clone(), state.clone());
-                    Box::new(obs)
+                    let obs = Obs::new(obs_conf.clone(), state.clone(), chat_sender.clone());
+                    Arc::new(obs)
                 }
                 config::SoftwareConnection::Obs(ref obs_conf) => {
-                    let obs = Obsv5::new(obs_conf.clone(), state.clone());
-                    Box::new(obs)
+                    let obs = Obsv5::new(obs_conf.clone(), state.clone(), chat_sender.clone());
+                    Arc::new(obs)
+                }
+                config::SoftwareConnection::Vmix(ref vmix_conf) => {
+                    let vmix = Vmix::new(vmix_conf.clone(), state.clone(), chat_sender.clone());
+                    Arc::new(vmix)
                 }
             };
 
             // Do i need this option here?
             w_state.broadcasting_software.connection = Some(connection);
+
+            let instances = w_state
+                .config
+                .additional_software
+                .iter()
+                .map(|instance| state::SoftwareInstance {
+                    name: instance.name.to_owned(),
+                    connection: Arc::new(Obsv5::new_instance(
+                        instance.name.to_owned(),
+                        instance.connection.clone(),
+                        state.clone(),
+                    )),
+                })
+                .collect();
+
+            w_state.broadcasting_software.instances = instances;
         }
 
-        // Add state to any OBS stream servers
-        let obs_state = state.clone();
         {
-            let mut r_state = state.write().await;
-            let stream_servers = &mut r_state.config.switcher.stream_servers;
-
-            for ss in stream_servers {
-                if let Some(obs) = ss
-                    .stream_server
-                    .as_any_mut()
-                    .downcast_mut::<stream_servers::Obs>()
-                {
-                    obs.state = Some(obs_state.clone());
-                    if let Some(scenes) = &ss.override_scenes {
-                        obs.scenes = Some(scenes.to_owned());
-                    }
+            let mut w_state = state.write().await;
+            let owner = w_state.config.user.name.to_owned();
+
+            for ss in &mut w_state.config.switcher.stream_servers {
+                if let Err(e) = Self::prepare_stream_server(&state, &owner, ss).await {
+                    error!("Unable to prepare stream server {}: {}", ss.name, e);
                 }
             }
         }
@@ -103,9 +114,152 @@ impl Noalbs {
         Ok(user)
     }
 
-    pub async fn add_stream_server(&self, stream_server: stream_servers::StreamServer) {
+    /// Adds state to OBS stream servers and starts builtin relays,
+    /// also when they are wrapped in a recorder
+    async fn prepare_stream_server(
+        state: &UserState,
+        owner: &str,
+        ss: &mut stream_servers::StreamServer,
+    ) -> Result<(), error::Error> {
+        let override_scenes = ss.override_scenes.to_owned();
+        let server = Self::inner_stream_server(ss);
+
+        if let Some(obs) = server.downcast_mut::<stream_servers::Obs>() {
+            obs.state = Some(state.clone());
+            if let Some(scenes) = override_scenes {
+                obs.scenes = Some(scenes);
+            }
+        }
+
+        if let Some(builtin) = server.downcast_mut::<stream_servers::Builtin>() {
+            if let Err(e) = builtin.start().await {
+                error!("Unable to start the builtin SRT relay: {}", e);
+            }
+        }
+
+        if let Some(push) = server.downcast_mut::<stream_servers::Push>() {
+            push.register(owner)
+                .map_err(|_| error::Error::PushIdTaken)?;
+        }
+
+        Ok(())
+    }
+
+    /// The stream server itself, or the server that's being recorded
+    fn inner_stream_server(ss: &mut stream_servers::StreamServer) -> &mut dyn std::any::Any {
+        let server = ss.stream_server.as_any_mut();
+
+        if server.is::<stream_servers::Recorder>() {
+            let recorder = server.downcast_mut::<stream_servers::Recorder>().unwrap();
+            return recorder.server.as_any_mut();
+        }
+
+        server
+    }
+
+    pub async fn add_stream_server(
+        &self,
+        mut stream_server: stream_servers::StreamServer,
+    ) -> Result<(), error::Error> {
+        // Hold the lock so a server with the same name can't be added meanwhile
         let mut state = self.state.write().await;
+
+        if state
+            .config
+            .switcher
+            .stream_servers
+            .iter()
+            .any(|s| s.name == stream_server.name)
+        {
+            return Err(error::Error::StreamServerAlreadyExists);
+        }
+
+        let is_obs = stream_server
+            .stream_server
+            .as_any_mut()
+            .is::<stream_servers::Obs>();
+
+        if is_obs && state.config.switcher.triggers.uses_bitrate() {
+            return Err(error::Error::BitrateTriggersWithObs);
+        }
+
+        let owner = state.config.user.name.to_owned();
+        Self::prepare_stream_server(&self.state, &owner, &mut stream_server).await?;
+
         state.config.switcher.add_stream_server(stream_server);
+        state.set_all_switchable_scenes();
+
+        Ok(())
+    }
+
+    pub async fn remove_stream_server(&self, name: &str) -> Result<(), error::Error> {
+        let mut state = self.state.write().await;
+        let servers = &mut state.config.switcher.stream_servers;
+        let index = servers
+            .iter()
+            .position(|s| s.name == name)
+            .ok_or(error::Error::StreamServerNotFound)?;
+
+        let mut removed = servers.remove(index);
+        state.set_all_switchable_scenes();
+
+        if let Some(push) =
+            Self::inner_stream_server(&mut removed).downcast_mut::<stream_servers::Push>()
+        {
+            push.unregister();
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_stream_server_enabled(
+        &self,
+        name: &str,
+        enabled: bool,
+    ) -> Result<(), error::Error> {
+        let mut state = self.state.write().await;
+        let server = state
+            .config
+            .switcher
+            .stream_servers
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or(error::Error::StreamServerNotFound)?;
+
+        server.enabled = enabled;
+
+        Ok(())
+    }
+
+    pub async fn set_stream_server_priority(
+        &self,
+        name: &str,
+        priority: i32,
+    ) -> Result<(), error::Error> {
+        let mut state = self.state.write().await;
+        let switcher = &mut state.config.switcher;
+        let server = switcher
+            .stream_servers
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or(error::Error::StreamServerNotFound)?;
+
+        server.priority = Some(priority);
+        switcher.sort_stream_servers();
+
+        Ok(())
+    }
+
+    pub async fn get_stream_servers(&self) -> Vec<stream_servers::StreamServerInfo> {
+        let state = self.state.read().await;
+
+        state
+            .config
+            .switcher
+            .stream_servers
+            .iter()
+            .map(stream_servers::StreamServerInfo::from)
+            .collect()
     }
 
     /// Runs a new switcher
@@ -127,6 +281,7 @@ impl Noalbs {
         let mut state = self.state.write().await;
         println!("> Stopping NOALBS {}", state.config.user.name);
         state.broadcasting_software.connection = None;
+        state.broadcasting_software.instances.clear();
 
         if let Some(handler) = &self.switcher_handler {
             info!("Stopping switcher");
@@ -134,6 +289,57 @@ impl Noalbs {
         }
     }
 
+    /// Current OBS connection details and if it's connected
+    pub async fn obs_connection(&self) -> Option<(config::ObsConfig, bool)> {
+        let state = self.state.read().await;
+        let connected = state.broadcasting_software.status == state::ClientStatus::Connected;
+
+        state
+            .config
+            .software
+            .obs_config()
+            .map(|c| (c.clone(), connected))
+    }
+
+    /// Changes the OBS connection details and reconnects without a restart,
+    /// no password keeps the current one and an empty password removes it
+    pub async fn set_obs_connection(
+        &self,
+        host: String,
+        port: u16,
+        password: Option<String>,
+    ) -> Result<(), error::Error> {
+        let mut state = self.state.write().await;
+
+        let mut obs_config = state
+            .config
+            .software
+            .obs_config()
+            .ok_or(error::Error::NotSupported)?
+            .clone();
+
+        obs_config.host = host;
+        obs_config.port = port;
+
+        if let Some(password) = password {
+            obs_config.password = Some(password).filter(|p| !p.is_empty());
+        }
+
+        state
+            .broadcasting_software
+            .connection
+            .as_ref()
+            .ok_or(error::Error::NoSoftwareSet)?
+            .reconfigure(obs_config.clone())
+            .await?;
+
+        if let Some(c) = state.config.software.obs_config_mut() {
+            *c = obs_config;
+        }
+
+        Ok(())
+    }
+
     pub async fn save_config(&self) -> Result<(), error::Error> {
         let state = self.state.read().await;
         self.storage.save(&state.config)
@@ -231,20 +437,23 @@ impl Noalbs {
         }
     }
 
-    pub async fn update_trigger(&self, kind: switcher::TriggerType, value: u32) -> Option<u32> {
+    pub async fn update_trigger(
+        &self,
+        kind: switcher::TriggerType,
+        value: u32,
+    ) -> Result<Option<u32>, error::Error> {
         let mut state = self.state.write().await;
-        let triggers = &mut state.config.switcher.triggers;
+        let switcher = &mut state.config.switcher;
 
         let real_value = if value == 0 { None } else { Some(value) };
+        let previous = std::mem::replace(switcher.triggers.get_mut(&kind), real_value);
 
-        match kind {
-            switcher::TriggerType::Low => triggers.low = real_value,
-            switcher::TriggerType::Rtt => triggers.rtt = real_value,
-            switcher::TriggerType::Offline => triggers.offline = real_value,
-            switcher::TriggerType::RttOffline => triggers.rtt_offline = real_value,
+        if let Err(e) = switcher.check_bitrate_triggers() {
+            *switcher.triggers.get_mut(&kind) = previous;
+            return Err(e);
         }
 
-        real_value
+        Ok(real_value)
     }
 
     pub async fn get_autostop(&self) -> Result<bool, error::Error> {
@@ -399,12 +608,7 @@ impl Noalbs {
     where
         T: serde::Serialize,
     {
-        let state = self.state.read().await;
-
-        for sender in &state.event_senders {
-            debug!("Sending event to {}", sender.token);
-            sender.send(&message);
-        }
+        self.state.read().await.send_event(message);
     }
 }
 
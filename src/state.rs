@@ -2,8 +2,9 @@ use std::{collections::HashSet, sync::Arc};
 
 use serde::Serialize;
 use tokio::sync::{mpsc, Notify};
+use tracing::debug;
 
-use crate::{broadcasting_software::BroadcastingSoftwareLogic, config};
+use crate::{broadcasting_software::BroadcastingSoftwareLogic, config, error, switcher};
 
 pub struct State {
     pub config: config::Config,
@@ -13,9 +14,37 @@ pub struct State {
 }
 
 impl State {
+    /// Sends the event to every connected WS client of the user
+    pub fn send_event<T>(&self, message: T)
+    where
+        T: Serialize,
+    {
+        for sender in &self.event_senders {
+            debug!("Sending event to {}", sender.token);
+            sender.send(&message);
+        }
+    }
+
+    /// Every scene used in the config
+    pub fn configured_scenes(&self) -> HashSet<&str> {
+        let optional = &self.config.optional_scenes;
+
+        self.switcher_state
+            .switchable_scenes
+            .iter()
+            .chain(optional.starting.iter())
+            .chain(optional.ending.iter())
+            .chain(optional.privacy.iter())
+            .chain(optional.refresh.iter())
+            .map(|s| s.as_str())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
     // also should be done once after loading config or adding stream_servers
     pub fn set_all_switchable_scenes(&mut self) {
         let all_scenes = &mut self.switcher_state.switchable_scenes;
+        all_scenes.clear();
 
         let scenes = &self.config.switcher.switching_scenes;
         all_scenes.insert(scenes.low.to_owned());
@@ -52,6 +81,9 @@ impl State {
 pub struct SwitcherState {
     pub last_used_server: Option<String>,
 
+    /// The switch that was last applied, so the same one isn't applied again
+    pub applied_switch: Option<switcher::AppliedSwitch>,
+
     /// All switchable scenes
     pub switchable_scenes: HashSet<String>,
 
@@ -72,6 +104,7 @@ impl Default for SwitcherState {
     fn default() -> Self {
         Self {
             last_used_server: None,
+            applied_switch: None,
             switcher_enabled_notifier: Arc::new(Notify::new()),
             switchable_scenes: HashSet::new(),
         }
@@ -83,12 +116,19 @@ pub struct BroadcastingSoftwareState {
     pub current_scene: String,
     pub status: ClientStatus,
     pub is_streaming: bool,
+
+    /// Studio mode is enabled in the broadcasting software
+    pub studio_mode: bool,
+
     pub last_stream_started_at: std::time::Instant,
     pub initial_stream_status: Option<StreamStatus>,
     pub stream_status: Option<StreamStatus>,
 
     // TODO?
-    pub connection: Option<Box<dyn BroadcastingSoftwareLogic>>,
+    pub connection: Option<Arc<dyn BroadcastingSoftwareLogic>>,
+
+    /// Connections to the additional software instances
+    pub instances: Vec<SoftwareInstance>,
 
     connected_notifier: Arc<Notify>,
     start_streaming_notifier: Arc<Notify>,
@@ -107,6 +147,34 @@ impl BroadcastingSoftwareState {
     pub fn switch_scene_notifier(&self) -> Arc<Notify> {
         self.switch_scene_notifier.clone()
     }
+
+    /// Connection of the additional instance with the name
+    pub fn instance(&self, name: &str) -> Option<&dyn BroadcastingSoftwareLogic> {
+        self.instances
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(name))
+            .map(|i| i.connection.as_ref())
+    }
+
+    /// Connection of the main software or of the additional instance
+    /// when a name is given
+    pub fn target(
+        &self,
+        instance: Option<&str>,
+    ) -> Result<&dyn BroadcastingSoftwareLogic, error::Error> {
+        match instance {
+            Some(name) => self.instance(name).ok_or(error::Error::InstanceNotFound),
+            None => self
+                .connection
+                .as_deref()
+                .ok_or(error::Error::UnableInitialConnection),
+        }
+    }
+}
+
+pub struct SoftwareInstance {
+    pub name: String,
+    pub connection: Arc<dyn BroadcastingSoftwareLogic>,
 }
 
 impl std::fmt::Debug for BroadcastingSoftwareState {
@@ -116,7 +184,9 @@ impl std::fmt::Debug for BroadcastingSoftwareState {
             .field("curent_scene", &self.current_scene)
             .field("status", &self.status)
             .field("is_streaming", &self.is_streaming)
+            .field("studio_mode", &self.studio_mode)
             .field("Does have a software set", &self.connection.is_some())
+            .field("instances", &self.instances.len())
             .finish()
     }
 }
@@ -128,7 +198,9 @@ impl Default for BroadcastingSoftwareState {
             current_scene: String::new(),
             status: ClientStatus::Disconnected,
             is_streaming: false,
+            studio_mode: false,
             connection: None,
+            instances: Vec::new(),
             connected_notifier: Arc::new(Notify::new()),
             start_streaming_notifier: Arc::new(Notify::new()),
             switch_scene_notifier: Arc::new(Notify::new()),
@@ -1,9 +1,8 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use tracing::{error, trace};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -22,28 +21,18 @@ pub struct Belabox {
     /// StreamID of the where you are publishing the feed. (ex; publish/live/feed1 )
     pub publisher: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
 }
 
 impl Belabox {
     pub async fn get_stats(&self) -> Option<Stat> {
-        let res = match self.client.get(&self.stats_url).send().await {
-            Ok(res) => res,
-            Err(e) => {
-                error!("Stats page is unreachable, {}", e);
-                return None;
-            }
-        };
-
-        if res.status() != reqwest::StatusCode::OK {
-            error!("Error accessing stats page ({})", self.stats_url);
-            return None;
-        }
-
-        let text = res.text().await.ok()?;
-        let data: Value = serde_json::from_str(&text).ok()?;
+        let data = stats_cache::get_json(&self.client, &self.stats_url).await?;
         let publisher = &data["publishers"][&self.publisher];
 
         let stream: Stat = match serde_json::from_value(publisher.to_owned()) {
@@ -125,13 +114,25 @@ impl StreamServersCommands for Belabox {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
 
-        let bitrate = format!("{} Kbps, {} ms", stats.bitrate, stats.rtt.round(),);
-        let dropped = format!("dropped {} packets", stats.dropped_pkts);
+        let info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with("rtt", stats.rtt)
+            .with("dropped", stats.dropped_pkts);
+
+        Some(info)
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
 
-        Some(format!("{} | {}", bitrate, dropped))
+        Some(super::Metrics {
+            bitrate: stats.bitrate.max(0) as u32,
+            rtt: Some(stats.rtt),
+            loss: Some(stats.dropped_pkts.max(0) as u64),
+        })
     }
 }
 
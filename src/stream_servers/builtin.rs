@@ -0,0 +1,563 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tracing::{debug, error, info, trace};
+
+use super::{Bsl, StreamServersCommands, SwitchLogic};
+use crate::{
+    error,
+    switcher::{SwitchType, Triggers},
+};
+
+const SRT_HEADER_SIZE: usize = 16;
+const SRT_CONTROL_ACK: u16 = 0x0002;
+const SRT_CONTROL_NAK: u16 = 0x0003;
+const MAX_PACKET_SIZE: usize = 1500;
+
+/// Seconds without receiving data before the stream is considered offline
+fn default_offline_timeout() -> u64 {
+    2
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Builtin {
+    /// Address the encoder connects to (ex; 0.0.0.0:9000 )
+    pub listen: String,
+
+    /// Address of the SRT listener the feed gets forwarded to, usually
+    /// an OBS media source (ex; 127.0.0.1:9001 )
+    pub forward: String,
+
+    /// Seconds without receiving data before the stream is considered offline
+    #[serde(default = "default_offline_timeout")]
+    pub offline_timeout: u64,
+
+    #[serde(skip)]
+    pub relay: Arc<Relay>,
+}
+
+#[derive(Default)]
+pub struct Relay {
+    stats: Mutex<RelayStats>,
+    local_addr: Mutex<Option<SocketAddr>>,
+    handle: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl Drop for Relay {
+    // Abort the spawned task
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+}
+
+pub struct RelayStats {
+    // Payload bytes received in the current window
+    window_bytes: u64,
+
+    // Start of the current window
+    window_start: Instant,
+
+    // Last time a data packet was received
+    last_data: Option<Instant>,
+
+    pub bitrate: u32,
+    pub rtt: f64,
+    pub packets: u64,
+    pub lost: u64,
+    pub retransmitted: u64,
+}
+
+impl Default for RelayStats {
+    fn default() -> Self {
+        Self {
+            window_bytes: 0,
+            window_start: Instant::now(),
+            last_data: None,
+            bitrate: 0,
+            rtt: 0.0,
+            packets: 0,
+            lost: 0,
+            retransmitted: 0,
+        }
+    }
+}
+
+impl RelayStats {
+    /// Calculates the bitrate once every second
+    fn roll_window(&mut self) {
+        let elapsed = self.window_start.elapsed();
+
+        if elapsed >= Duration::from_secs(1) {
+            let bits = self.window_bytes as f64 * 8.0;
+            self.bitrate = (bits / elapsed.as_secs_f64() / 1024.0) as u32;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        }
+    }
+
+    /// Packets send by the encoder
+    fn on_client_packet(&mut self, packet: &[u8]) {
+        if packet.len() < SRT_HEADER_SIZE || is_control(packet) {
+            return;
+        }
+
+        // Retransmitted flag in the message number field
+        if packet[4] & 0x04 != 0 {
+            self.retransmitted += 1;
+        }
+
+        self.packets += 1;
+        self.window_bytes += (packet.len() - SRT_HEADER_SIZE) as u64;
+        self.last_data = Some(Instant::now());
+        self.roll_window();
+    }
+
+    /// Packets send by the receiving side
+    fn on_upstream_packet(&mut self, packet: &[u8]) {
+        if packet.len() < SRT_HEADER_SIZE || !is_control(packet) {
+            return;
+        }
+
+        let control_type = u16::from_be_bytes([packet[0], packet[1]]) & 0x7fff;
+        let cif = &packet[SRT_HEADER_SIZE..];
+
+        match control_type {
+            // Light ACKs don't contain the RTT
+            SRT_CONTROL_ACK if cif.len() >= 8 => {
+                let rtt = u32::from_be_bytes([cif[4], cif[5], cif[6], cif[7]]);
+                self.rtt = rtt as f64 / 1000.0;
+            }
+            SRT_CONTROL_NAK => self.lost += count_lost(cif),
+            _ => {}
+        }
+    }
+
+    fn is_online(&mut self, timeout: Duration) -> bool {
+        self.roll_window();
+
+        self.last_data.is_some_and(|l| l.elapsed() <= timeout)
+    }
+}
+
+fn is_control(packet: &[u8]) -> bool {
+    packet[0] & 0x80 != 0
+}
+
+/// Counts the lost packets in a NAK loss list
+fn count_lost(cif: &[u8]) -> u64 {
+    let mut words = cif
+        .chunks_exact(4)
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]));
+
+    let mut lost = 0;
+
+    while let Some(word) = words.next() {
+        if word & 0x8000_0000 == 0 {
+            lost += 1;
+            continue;
+        }
+
+        // A range of lost packets
+        let start = word & 0x7fff_ffff;
+        if let Some(end) = words.next() {
+            lost += (end.wrapping_sub(start) & 0x7fff_ffff) as u64 + 1;
+        }
+    }
+
+    lost
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    pub bitrate: u32,
+    pub rtt: f64,
+    pub packets: u64,
+    pub lost: u64,
+    pub retransmitted: u64,
+}
+
+impl Builtin {
+    /// Binds the listener and starts relaying to the forward address
+    pub async fn start(&self) -> Result<(), error::Error> {
+        let listener = UdpSocket::bind(&self.listen).await?;
+        let local_addr = listener.local_addr()?;
+        let forward = tokio::net::lookup_host(&self.forward)
+            .await?
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "Forward address not found")
+            })?;
+
+        info!("Relaying SRT from {} to {}", local_addr, forward);
+
+        *self.relay.local_addr.lock().unwrap() = Some(local_addr);
+
+        let relay = Arc::downgrade(&self.relay);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = Self::run(listener, forward, relay).await {
+                error!("SRT relay stopped: {}", e);
+            }
+        });
+
+        if let Some(old) = self.relay.handle.lock().unwrap().replace(handle) {
+            old.abort();
+        }
+
+        Ok(())
+    }
+
+    /// The address the relay is listening on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        *self.relay.local_addr.lock().unwrap()
+    }
+
+    async fn run(
+        listener: UdpSocket,
+        forward: SocketAddr,
+        relay: std::sync::Weak<Relay>,
+    ) -> Result<(), error::Error> {
+        let mut upstream = Self::connect_upstream(forward).await?;
+        let mut client: Option<SocketAddr> = None;
+
+        let mut client_buf = [0u8; MAX_PACKET_SIZE];
+        let mut upstream_buf = [0u8; MAX_PACKET_SIZE];
+
+        // Errors of a single packet don't stop the relay, it only stops
+        // when the stream server got removed
+        while relay.strong_count() > 0 {
+            tokio::select! {
+                res = listener.recv_from(&mut client_buf) => {
+                    let (len, addr) = match res {
+                        Ok(res) => res,
+                        Err(e) => {
+                            // Windows reports a gone encoder as a connection reset
+                            debug!("Unable to receive from the encoder: {}", e);
+                            continue;
+                        }
+                    };
+                    let packet = &client_buf[..len];
+
+                    // A new connection from the encoder gets a fresh upstream socket
+                    if client.is_some_and(|c| c != addr) {
+                        debug!("New SRT connection from {}", addr);
+
+                        match Self::connect_upstream(forward).await {
+                            Ok(socket) => upstream = socket,
+                            Err(e) => error!("Unable to connect to {}: {}", forward, e),
+                        }
+                    }
+                    client = Some(addr);
+
+                    let Some(relay) = relay.upgrade() else { break };
+                    relay.stats.lock().unwrap().on_client_packet(packet);
+
+                    if let Err(e) = upstream.send(packet).await {
+                        trace!("Unable to forward SRT packet: {}", e);
+                    }
+                }
+                res = upstream.recv(&mut upstream_buf) => {
+                    let len = match res {
+                        Ok(len) => len,
+                        Err(e) => {
+                            // Nothing listening on the forward address yet
+                            trace!("Unable to receive from upstream: {}", e);
+                            continue;
+                        }
+                    };
+                    let packet = &upstream_buf[..len];
+
+                    let Some(relay) = relay.upgrade() else { break };
+                    relay.stats.lock().unwrap().on_upstream_packet(packet);
+
+                    if let Some(addr) = client {
+                        if let Err(e) = listener.send_to(packet, addr).await {
+                            debug!("Unable to send to the encoder: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn connect_upstream(forward: SocketAddr) -> Result<UdpSocket, error::Error> {
+        let bind: SocketAddr = if forward.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(forward).await?;
+
+        Ok(socket)
+    }
+
+    pub fn get_stats(&self) -> Option<Stats> {
+        let mut stats = self.relay.stats.lock().unwrap();
+
+        if !stats.is_online(Duration::from_secs(self.offline_timeout)) {
+            return None;
+        }
+
+        let stats = Stats {
+            bitrate: stats.bitrate,
+            rtt: stats.rtt,
+            packets: stats.packets,
+            lost: stats.lost,
+            retransmitted: stats.retransmitted,
+        };
+
+        trace!("{:#?}", stats);
+        Some(stats)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl SwitchLogic for Builtin {
+    /// Which scene to switch to
+    async fn switch(&self, triggers: &Triggers) -> SwitchType {
+        let stats = match self.get_stats() {
+            Some(b) => b,
+            None => return SwitchType::Offline,
+        };
+
+        if let Some(offline) = triggers.offline {
+            if stats.bitrate > 0 && stats.bitrate <= offline {
+                return SwitchType::Offline;
+            }
+        }
+
+        if let Some(rtt_offline) = triggers.rtt_offline {
+            if stats.rtt >= rtt_offline.into() {
+                return SwitchType::Offline;
+            }
+        }
+
+        if stats.bitrate == 0 {
+            return SwitchType::Previous;
+        }
+
+        if let Some(low) = triggers.low {
+            if stats.bitrate <= low {
+                return SwitchType::Low;
+            }
+        }
+
+        if let Some(rtt) = triggers.rtt {
+            if stats.rtt >= rtt.into() {
+                return SwitchType::Low;
+            }
+        }
+
+        SwitchType::Normal
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl StreamServersCommands for Builtin {
+    async fn bitrate(&self) -> super::Bitrate {
+        let stats = match self.get_stats() {
+            Some(stats) => stats,
+            None => return super::Bitrate { message: None },
+        };
+
+        let message = format!("{}, {} ms", stats.bitrate, stats.rtt.round());
+        super::Bitrate {
+            message: Some(message),
+        }
+    }
+
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        let stats = self.get_stats()?;
+
+        let info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with("rtt", stats.rtt)
+            .with("lost", stats.lost)
+            .with("retransmitted", stats.retransmitted)
+            .with("packets", stats.packets);
+
+        Some(info)
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats()?;
+
+        Some(super::Metrics {
+            bitrate: stats.bitrate,
+            rtt: Some(stats.rtt),
+            loss: Some(stats.lost),
+        })
+    }
+}
+
+#[typetag::serde]
+impl Bsl for Builtin {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_packet(seq: u32, payload: usize, retransmitted: bool) -> Vec<u8> {
+        let mut packet = vec![0u8; SRT_HEADER_SIZE + payload];
+        packet[0..4].copy_from_slice(&(seq & 0x7fff_ffff).to_be_bytes());
+
+        if retransmitted {
+            packet[4] |= 0x04;
+        }
+
+        packet
+    }
+
+    fn control_packet(control_type: u16, cif: &[u32]) -> Vec<u8> {
+        let mut packet = vec![0u8; SRT_HEADER_SIZE];
+        packet[0..2].copy_from_slice(&(0x8000 | control_type).to_be_bytes());
+
+        for word in cif {
+            packet.extend_from_slice(&word.to_be_bytes());
+        }
+
+        packet
+    }
+
+    #[test]
+    fn nak_loss_list() {
+        // Single packet 5 and the range 10 till 14
+        let cif = [5u32, 0x8000_000a, 14]
+            .iter()
+            .flat_map(|w| w.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        assert_eq!(count_lost(&cif), 6);
+    }
+
+    #[test]
+    fn parse_packets() {
+        let mut stats = RelayStats::default();
+
+        stats.on_client_packet(&data_packet(1, 1316, false));
+        stats.on_client_packet(&data_packet(2, 1316, true));
+        stats.on_upstream_packet(&control_packet(SRT_CONTROL_ACK, &[3, 45_500, 1000, 8192]));
+        stats.on_upstream_packet(&control_packet(SRT_CONTROL_NAK, &[7]));
+
+        assert_eq!(stats.packets, 2);
+        assert_eq!(stats.retransmitted, 1);
+        assert_eq!(stats.window_bytes, 2632);
+        assert_eq!(stats.rtt, 45.5);
+        assert_eq!(stats.lost, 1);
+        assert!(stats.is_online(Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn relay_on_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let encoder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let builtin = Builtin {
+            listen: "127.0.0.1:0".to_string(),
+            forward: receiver.local_addr().unwrap().to_string(),
+            offline_timeout: default_offline_timeout(),
+            relay: Default::default(),
+        };
+
+        assert!(builtin.get_stats().is_none());
+
+        builtin.start().await.unwrap();
+        let relay_addr = builtin.local_addr().unwrap();
+
+        let timeout = Duration::from_secs(2);
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        // Encoder -> relay -> receiver
+        let packet = data_packet(1, 1316, false);
+        encoder.send_to(&packet, relay_addr).await.unwrap();
+        let (len, upstream_addr) = tokio::time::timeout(timeout, receiver.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], &packet[..]);
+
+        // Receiver -> relay -> encoder
+        let ack = control_packet(SRT_CONTROL_ACK, &[2, 120_000, 1000, 8192]);
+        receiver.send_to(&ack, upstream_addr).await.unwrap();
+        let len = tokio::time::timeout(timeout, encoder.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], &ack[..]);
+
+        let stats = builtin.get_stats().unwrap();
+        assert_eq!(stats.packets, 1);
+        assert_eq!(stats.rtt, 120.0);
+    }
+
+    #[tokio::test]
+    async fn encoder_reconnects() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let builtin = Builtin {
+            listen: "127.0.0.1:0".to_string(),
+            forward: receiver.local_addr().unwrap().to_string(),
+            offline_timeout: default_offline_timeout(),
+            relay: Default::default(),
+        };
+
+        builtin.start().await.unwrap();
+        let relay_addr = builtin.local_addr().unwrap();
+
+        let timeout = Duration::from_secs(2);
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        let encoder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        encoder
+            .send_to(&data_packet(1, 1316, false), relay_addr)
+            .await
+            .unwrap();
+        let (_, upstream_addr) = tokio::time::timeout(timeout, receiver.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The encoder goes away while the receiver still sends to it
+        drop(encoder);
+        let ack = control_packet(SRT_CONTROL_ACK, &[2, 120_000, 1000, 8192]);
+        receiver.send_to(&ack, upstream_addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // And connects again from another port
+        let encoder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let packet = data_packet(2, 1316, false);
+        encoder.send_to(&packet, relay_addr).await.unwrap();
+        let (len, upstream_addr) = tokio::time::timeout(timeout, receiver.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], &packet[..]);
+
+        receiver.send_to(&ack, upstream_addr).await.unwrap();
+        let len = tokio::time::timeout(timeout, encoder.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], &ack[..]);
+
+        assert_eq!(builtin.get_stats().unwrap().packets, 2);
+    }
+}
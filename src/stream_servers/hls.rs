@@ -0,0 +1,488 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::{error, trace};
+
+use super::{stats_cache, Bsl, HttpClient, StreamServersCommands, SwitchLogic};
+use crate::switcher::{SwitchType, Triggers};
+
+/// Amount of measured segments used to calculate the bitrate
+const SEGMENT_WINDOW: usize = 3;
+
+#[derive(Debug, PartialEq)]
+enum Playlist {
+    Master(Vec<String>),
+    Media(MediaPlaylist),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct MediaPlaylist {
+    media_sequence: u64,
+    target_duration: f64,
+    segments: Vec<Segment>,
+    ended: bool,
+}
+
+impl MediaPlaylist {
+    /// The media sequence number the next segment will get
+    fn next_sequence(&self) -> u64 {
+        self.media_sequence + self.segments.len() as u64
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Segment {
+    duration: f64,
+    uri: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub bitrate: u32,
+    pub media_sequence: u64,
+    pub target_duration: f64,
+    pub segment_duration: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hls {
+    /// URL to the HLS playlist (ex; http://localhost:8080/hls/live.m3u8 )
+    pub playlist_url: String,
+
+    /// Seconds without a new segment before the stream is considered offline.
+    /// Defaults to three times the target duration of the playlist.
+    pub offline_timeout: Option<u64>,
+
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<Stats>,
+
+    #[serde(skip)]
+    pub cache: Arc<Mutex<Cache>>,
+}
+
+pub struct Cache {
+    // The next media sequence number of the last fetched playlist
+    pub next_sequence: Option<u64>,
+
+    // The last time a new segment showed up in the playlist
+    pub last_advanced: Instant,
+
+    // The next media sequence number that still needs to be measured
+    pub next_measured: u64,
+
+    // Duration and size in bytes of the most recent segments
+    pub segments: VecDeque<(f64, u64)>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            next_sequence: None,
+            last_advanced: Instant::now(),
+            next_measured: 0,
+            segments: VecDeque::with_capacity(SEGMENT_WINDOW),
+        }
+    }
+}
+
+impl Cache {
+    /// Keeps track of when the playlist got a new segment, a lower sequence
+    /// number means the encoder restarted so everything starts over
+    fn advance(&mut self, next_sequence: u64, now: Instant) {
+        match self.next_sequence {
+            Some(n) if next_sequence < n => {
+                self.next_measured = 0;
+                self.segments.clear();
+            }
+            Some(n) if next_sequence == n => return,
+            _ => {}
+        }
+
+        self.next_sequence = Some(next_sequence);
+        self.last_advanced = now;
+    }
+
+    fn bitrate(&self) -> u32 {
+        let duration = self.segments.iter().map(|(d, _)| d).sum::<f64>();
+
+        if duration <= 0.0 {
+            return 0;
+        }
+
+        let bits = self.segments.iter().map(|(_, b)| b).sum::<u64>() * 8;
+        (bits as f64 / duration / 1024.0) as u32
+    }
+}
+
+impl Hls {
+    pub async fn get_stats(&self) -> Option<Stats> {
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
+
+    async fn fetch_stats(&self) -> Option<Stats> {
+        let mut url = reqwest::Url::parse(&self.playlist_url).ok()?;
+        let mut playlist = self.get_playlist(&url).await?;
+
+        // Use the first variant when pointed at a master playlist
+        if let Playlist::Master(variants) = &playlist {
+            url = url.join(variants.first()?).ok()?;
+            playlist = self.get_playlist(&url).await?;
+        }
+
+        let Playlist::Media(playlist) = playlist else {
+            error!("No media playlist found ({})", self.playlist_url);
+            return None;
+        };
+
+        let to_measure = {
+            let mut cache = self.cache.lock().unwrap();
+            let next_sequence = playlist.next_sequence();
+
+            cache.advance(next_sequence, Instant::now());
+
+            let timeout = match self.offline_timeout {
+                Some(t) => Duration::from_secs(t),
+                None => Duration::from_secs_f64(playlist.target_duration.max(1.0) * 3.0),
+            };
+
+            if playlist.ended || cache.last_advanced.elapsed() > timeout {
+                return None;
+            }
+
+            // Only measure the newest segment on the first poll
+            let first_new = cache
+                .next_measured
+                .max(playlist.media_sequence)
+                .max(next_sequence.saturating_sub(1));
+            cache.next_measured = next_sequence;
+
+            playlist
+                .segments
+                .iter()
+                .zip(playlist.media_sequence..)
+                .filter(|(_, seq)| *seq >= first_new)
+                .filter_map(|(segment, _)| Some((segment.duration, url.join(&segment.uri).ok()?)))
+                .collect::<Vec<_>>()
+        };
+
+        let mut measured = Vec::new();
+        for (duration, segment_url) in to_measure {
+            if let Some(size) = self.get_segment_size(segment_url).await {
+                measured.push((duration, size));
+            }
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        for segment in measured {
+            if cache.segments.len() == SEGMENT_WINDOW {
+                cache.segments.pop_front();
+            }
+
+            cache.segments.push_back(segment);
+        }
+
+        let stats = Stats {
+            bitrate: cache.bitrate(),
+            media_sequence: playlist.media_sequence,
+            target_duration: playlist.target_duration,
+            segment_duration: playlist.segments.last().map_or(0.0, |s| s.duration),
+        };
+
+        trace!("{:#?}", stats);
+        Some(stats)
+    }
+
+    async fn get_playlist(&self, url: &reqwest::Url) -> Option<Playlist> {
+        let res = match self.client.get(url.clone()).send().await {
+            Ok(res) => res,
+            Err(_) => {
+                error!("Playlist ({}) is unreachable", url);
+                return None;
+            }
+        };
+
+        if res.status() != reqwest::StatusCode::OK {
+            // Most servers remove the playlist when the stream stops
+            trace!("Error accessing playlist ({})", url);
+            return None;
+        }
+
+        let text = res.text().await.ok()?;
+
+        match parse_playlist(&text) {
+            Some(p) => Some(p),
+            None => {
+                trace!("{}", &text);
+                error!("Error parsing playlist ({})", url);
+                None
+            }
+        }
+    }
+
+    /// Gets the size of a segment using a HEAD request, falls back to
+    /// downloading the segment when the server doesn't send a length.
+    async fn get_segment_size(&self, url: reqwest::Url) -> Option<u64> {
+        if let Ok(res) = self.client.head(url.clone()).send().await {
+            let length = res
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| l.parse::<u64>().ok());
+
+            if res.status() == reqwest::StatusCode::OK && length.is_some() {
+                return length;
+            }
+        }
+
+        let res = match self.client.get(url.clone()).send().await {
+            Ok(res) => res,
+            Err(_) => {
+                error!("Segment ({}) is unreachable", url);
+                return None;
+            }
+        };
+
+        if res.status() != reqwest::StatusCode::OK {
+            return None;
+        }
+
+        res.bytes().await.ok().map(|b| b.len() as u64)
+    }
+}
+
+fn parse_playlist(text: &str) -> Option<Playlist> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+
+    if lines.next()? != "#EXTM3U" {
+        return None;
+    }
+
+    let mut variants = Vec::new();
+    let mut media = MediaPlaylist::default();
+    let mut is_variant = false;
+    let mut duration = None;
+
+    for line in lines {
+        if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            media.media_sequence = value.parse().ok()?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            media.target_duration = value.parse().ok()?;
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let value = value.split(',').next()?;
+            duration = Some(value.parse::<f64>().ok()?);
+        } else if line.starts_with("#EXT-X-STREAM-INF") {
+            is_variant = true;
+        } else if line == "#EXT-X-ENDLIST" {
+            media.ended = true;
+        } else if !line.starts_with('#') {
+            if is_variant {
+                variants.push(line.to_owned());
+                is_variant = false;
+            } else if let Some(duration) = duration.take() {
+                media.segments.push(Segment {
+                    duration,
+                    uri: line.to_owned(),
+                });
+            }
+        }
+    }
+
+    if !variants.is_empty() {
+        return Some(Playlist::Master(variants));
+    }
+
+    Some(Playlist::Media(media))
+}
+
+#[async_trait]
+#[typetag::serde]
+impl SwitchLogic for Hls {
+    /// Which scene to switch to
+    async fn switch(&self, triggers: &Triggers) -> SwitchType {
+        let stats = match self.get_stats().await {
+            Some(b) => b,
+            None => return SwitchType::Offline,
+        };
+
+        if let Some(offline) = triggers.offline {
+            if stats.bitrate > 0 && stats.bitrate <= offline {
+                return SwitchType::Offline;
+            }
+        }
+
+        if stats.bitrate == 0 {
+            return SwitchType::Previous;
+        }
+
+        if let Some(low) = triggers.low {
+            if stats.bitrate <= low {
+                return SwitchType::Low;
+            }
+        }
+
+        SwitchType::Normal
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl StreamServersCommands for Hls {
+    async fn bitrate(&self) -> super::Bitrate {
+        let stats = match self.get_stats().await {
+            Some(stats) => stats,
+            None => return super::Bitrate { message: None },
+        };
+
+        super::Bitrate {
+            message: Some(format!("{}", stats.bitrate)),
+        }
+    }
+
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        let stats = self.get_stats().await?;
+
+        let info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with_values(
+                "segment",
+                [
+                    ("sequence", stats.media_sequence.into()),
+                    ("duration", stats.segment_duration.into()),
+                    ("target", stats.target_duration.into()),
+                ],
+            );
+
+        Some(info)
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
+
+        Some(super::Metrics {
+            bitrate: stats.bitrate,
+            ..Default::default()
+        })
+    }
+}
+
+#[typetag::serde]
+impl Bsl for Hls {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_playlist() {
+        let text = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:2
+#EXT-X-MEDIA-SEQUENCE:1337
+#EXTINF:2.000,
+live-1337.ts
+#EXTINF:1.960,
+live-1338.ts
+";
+
+        let parsed = parse_playlist(text).unwrap();
+        let Playlist::Media(media) = parsed else {
+            panic!("Should be a media playlist");
+        };
+
+        assert_eq!(media.media_sequence, 1337);
+        assert_eq!(media.target_duration, 2.0);
+        assert_eq!(media.segments.len(), 2);
+        assert_eq!(media.segments[1].uri, "live-1338.ts");
+        assert_eq!(media.next_sequence(), 1339);
+        assert!(!media.ended);
+    }
+
+    #[test]
+    fn master_playlist() {
+        let text = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080
+source/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1500000,RESOLUTION=854x480
+480p/index.m3u8
+";
+
+        let parsed = parse_playlist(text).unwrap();
+        assert_eq!(
+            parsed,
+            Playlist::Master(vec![
+                "source/index.m3u8".to_string(),
+                "480p/index.m3u8".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn ended_playlist() {
+        let text = "#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXTINF:4.0,
+a.ts
+#EXT-X-ENDLIST
+";
+
+        let Some(Playlist::Media(media)) = parse_playlist(text) else {
+            panic!("Should be a media playlist");
+        };
+
+        assert_eq!(media.media_sequence, 0);
+        assert!(media.ended);
+    }
+
+    #[test]
+    fn not_a_playlist() {
+        assert!(parse_playlist("<html></html>").is_none());
+    }
+
+    #[test]
+    fn bitrate_from_segments() {
+        let mut cache = Cache::default();
+        cache.segments.push_back((2.0, 1_024_000));
+        cache.segments.push_back((2.0, 1_024_000));
+
+        assert_eq!(cache.bitrate(), 4000);
+    }
+
+    #[test]
+    fn sequence_reset() {
+        let start = Instant::now();
+        let mut cache = Cache::default();
+
+        cache.advance(500, start);
+        cache.next_measured = 500;
+        cache.segments.push_back((2.0, 1_024_000));
+
+        let later = start + Duration::from_secs(10);
+        cache.advance(500, later);
+        assert_eq!(cache.last_advanced, start);
+
+        // Encoder restarted
+        cache.advance(1, later);
+        assert_eq!(cache.next_sequence, Some(1));
+        assert_eq!(cache.last_advanced, later);
+        assert_eq!(cache.next_measured, 0);
+        assert!(cache.segments.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+/// HTTP options shared by every HTTP based stream server
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<HttpAuth>,
+
+    /// Extra headers send with every request
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Request timeout in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// Path to a PEM encoded CA certificate to trust
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+
+    /// Accept invalid TLS certificates
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HttpAuth {
+    Bearer { token: String },
+    Basic { username: String, password: String },
+}
+
+/// Username and password of the old `auth` field of a stream server
+#[derive(Debug, Deserialize)]
+pub struct LegacyAuth {
+    username: String,
+    password: String,
+}
+
+impl HttpOptions {
+    /// Moves the old `auth` field of a stream server into the options,
+    /// setting it next to the `auth` of the options would send two
+    /// authorization headers so that gets refused
+    pub fn with_legacy_auth(mut self, legacy: Option<LegacyAuth>) -> Result<Self, String> {
+        let Some(LegacyAuth { username, password }) = legacy else {
+            return Ok(self);
+        };
+
+        if self.auth.is_some() {
+            return Err("Both auth and http.auth are set, only use http.auth".to_string());
+        }
+
+        self.auth = Some(HttpAuth::Basic { username, password });
+        Ok(self)
+    }
+
+    fn build_client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder().user_agent(APP_USER_AGENT);
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Unable to read CA certificate {}: {}", path, e))?;
+            let cert = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+
+            builder = builder.add_root_certificate(cert);
+        }
+
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder.build().map_err(|e| e.to_string())
+    }
+}
+
+/// Client to make HTTP requests with, configured by the `http` options
+/// of a stream server
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    options: HttpOptions,
+    client: reqwest::Client,
+}
+
+impl HttpClient {
+    pub fn new(options: HttpOptions) -> Result<Self, String> {
+        let client = options.build_client()?;
+
+        Ok(Self { options, client })
+    }
+
+    pub fn options(&self) -> &HttpOptions {
+        &self.options
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.options == HttpOptions::default()
+    }
+
+    pub fn get<U: reqwest::IntoUrl>(&self, url: U) -> reqwest::RequestBuilder {
+        self.apply(self.client.get(url))
+    }
+
+    pub fn head<U: reqwest::IntoUrl>(&self, url: U) -> reqwest::RequestBuilder {
+        self.apply(self.client.head(url))
+    }
+
+    pub fn post<U: reqwest::IntoUrl>(&self, url: U) -> reqwest::RequestBuilder {
+        self.apply(self.client.post(url))
+    }
+
+    fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        for (name, value) in &self.options.headers {
+            request = request.header(name, value);
+        }
+
+        match &self.options.auth {
+            Some(HttpAuth::Bearer { token }) => request.bearer_auth(token),
+            Some(HttpAuth::Basic { username, password }) => {
+                request.basic_auth(username, Some(password))
+            }
+            None => request,
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(HttpOptions::default()).expect("Failed to create reqwest client")
+    }
+}
+
+impl Serialize for HttpClient {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.options.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HttpClient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let options = HttpOptions::deserialize(deserializer)?;
+
+        Self::new(options).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_roundtrip() {
+        let json = r#"{"auth":{"type":"bearer","token":"secret"},"headers":{"X-Api-Key":"key"},"timeout":2000}"#;
+        let client: HttpClient = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            client.options().auth,
+            Some(HttpAuth::Bearer {
+                token: "secret".to_string()
+            })
+        );
+        assert_eq!(serde_json::to_string(&client).unwrap(), json);
+        assert_eq!(serde_json::to_string(&HttpClient::default()).unwrap(), "{}");
+    }
+
+    #[test]
+    fn applies_auth_and_headers() {
+        let client = HttpClient::new(HttpOptions {
+            auth: Some(HttpAuth::Basic {
+                username: "user".to_string(),
+                password: "pass".to_string(),
+            }),
+            headers: BTreeMap::from([("X-Api-Key".to_string(), "key".to_string())]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let request = client.get("http://localhost/stats").build().unwrap();
+        let headers = request.headers();
+
+        assert_eq!(headers["x-api-key"], "key");
+        assert_eq!(headers["authorization"], "Basic dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn legacy_auth_moved() {
+        let legacy = || serde_json::from_str(r#"{"username":"user","password":"pass"}"#).ok();
+
+        let options = HttpOptions::default().with_legacy_auth(legacy()).unwrap();
+        assert_eq!(
+            options.auth,
+            Some(HttpAuth::Basic {
+                username: "user".to_string(),
+                password: "pass".to_string(),
+            })
+        );
+
+        let options = HttpOptions {
+            auth: Some(HttpAuth::Bearer {
+                token: "secret".to_string(),
+            }),
+            ..Default::default()
+        };
+        assert!(options.with_legacy_auth(legacy()).is_err());
+    }
+
+    #[test]
+    fn missing_ca_cert() {
+        let json = r#"{"caCert":"/does/not/exist.pem"}"#;
+        assert!(serde_json::from_str::<HttpClient>(json).is_err());
+    }
+}
@@ -4,7 +4,9 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use tracing::{error, trace};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{
+    http::LegacyAuth, stats_cache, Bsl, HttpClient, HttpOptions, StreamServersCommands, SwitchLogic,
+};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -15,7 +17,7 @@ pub struct StreamStats {
     pub bytes_received: u64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SrtStats {
     pub id: String,
@@ -94,35 +96,60 @@ pub struct Source {
     pub id: String,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Stats {
     pub bitrate: u32,
     pub srt: Option<SrtStats>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Auth {
-    username: String,
-    password: String,
-}
-
 #[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", try_from = "MediamtxConfig")]
 pub struct Mediamtx {
     /// URL to MediaMTX stats page (ex; http://localhost:9997/v3/paths/get/mystream )
     pub stats_url: String,
 
-    pub auth: Option<Auth>,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<Stats>,
 
     #[serde(skip)]
     pub cache: Arc<Mutex<Cache>>,
 }
 
+/// MediaMTX as written in the config, which can still have the old
+/// `auth` field
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MediamtxConfig {
+    stats_url: String,
+    auth: Option<LegacyAuth>,
+    #[serde(default)]
+    http: HttpOptions,
+}
+
+impl TryFrom<MediamtxConfig> for Mediamtx {
+    type Error = String;
+
+    fn try_from(config: MediamtxConfig) -> Result<Self, Self::Error> {
+        let options = config.http.with_legacy_auth(config.auth)?;
+
+        Ok(Self {
+            stats_url: config.stats_url,
+            client: HttpClient::new(options)?,
+            recent: Default::default(),
+            cache: Default::default(),
+        })
+    }
+}
+
 pub struct Cache {
     // The last total bytes received
     pub prev_bytes_received: u64,
@@ -146,13 +173,11 @@ impl Default for Cache {
 
 impl Mediamtx {
     pub async fn get_stats(&self) -> Option<Stats> {
-        let mut request = self.client.get(&self.stats_url);
-
-        if let Some(auth) = &self.auth {
-            request = request.basic_auth(&auth.username, Some(&auth.password));
-        }
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
 
-        let res = match request.send().await {
+    async fn fetch_stats(&self) -> Option<Stats> {
+        let res = match self.client.get(&self.stats_url).send().await {
             Ok(res) => res,
             Err(_) => {
                 error!("Stats page ({}) is unreachable", self.stats_url);
@@ -211,13 +236,7 @@ impl Mediamtx {
         let stats_url: Vec<&str> = self.stats_url.split("/v3").collect();
         let stats_url = format!("{}/v3/srtconns/get/{id}", stats_url.first()?);
 
-        let mut request = self.client.get(stats_url.clone());
-
-        if let Some(auth) = &self.auth {
-            request = request.basic_auth(&auth.username, Some(&auth.password));
-        }
-
-        let res = match request.send().await {
+        let res = match self.client.get(stats_url.clone()).send().await {
             Ok(res) => res,
             Err(_) => {
                 error!("Stats page ({}) is unreachable", stats_url);
@@ -306,32 +325,39 @@ impl StreamServersCommands for Mediamtx {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
-        let bitrate = stats.bitrate;
 
-        let mut info = format!("{} Kbps", bitrate);
+        let mut info = super::SourceInfo::default().with("bitrate", stats.bitrate);
 
         if let Some(srt) = stats.srt {
-            let rtt = format!(", {} ms", srt.ms_rtt.round());
-
-            let mbps = format!("Receiving rate {:.2} Mbps", srt.mbps_receive_rate);
-
-            let pkt = format!(
-                "dropped {}, loss {}, retrans {}",
-                srt.packets_received_drop, srt.packets_received_loss, srt.packets_received_retrans
-            );
-
-            let latency = format!(
-                "Latency send {} ms receive {} ms",
-                srt.ms_send_tsb_pd_delay, srt.ms_receive_tsb_pd_delay
-            );
-
-            info += &format!("{} | {} | {} | {}", rtt, mbps, pkt, latency);
+            info = info
+                .with("rtt", srt.ms_rtt)
+                .with("receiveRate", srt.mbps_receive_rate)
+                .with("dropped", srt.packets_received_drop)
+                .with("lost", srt.packets_received_loss)
+                .with("retransmitted", srt.packets_received_retrans)
+                .with_values(
+                    "latency",
+                    [
+                        ("send", srt.ms_send_tsb_pd_delay.into()),
+                        ("receive", srt.ms_receive_tsb_pd_delay.into()),
+                    ],
+                );
         }
 
         Some(info)
     }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
+
+        Some(super::Metrics {
+            bitrate: stats.bitrate,
+            rtt: stats.srt.as_ref().map(|s| s.ms_rtt),
+            loss: stats.srt.as_ref().map(|s| s.packets_received_loss),
+        })
+    }
 }
 
 #[typetag::serde]
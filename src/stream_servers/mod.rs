@@ -1,4 +1,4 @@
-use std::any::Any;
+use std::{any::Any, collections::BTreeMap};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -6,21 +6,34 @@ use serde::{Deserialize, Serialize};
 use crate::switcher;
 
 pub mod belabox;
+pub mod builtin;
+pub mod hls;
+pub mod http;
 pub mod mediamtx;
 pub mod nginx;
 pub mod nimble;
 pub mod nms;
 pub mod obs;
+pub mod push;
+pub mod recorder;
+pub mod replay;
 pub mod rist;
 pub mod sls;
+pub mod stats_cache;
 pub mod xiu;
 
 pub use belabox::Belabox;
+pub use builtin::Builtin;
+pub use hls::Hls;
+pub use http::{HttpClient, HttpOptions};
 pub use mediamtx::Mediamtx;
 pub use nginx::Nginx;
 pub use nimble::Nimble;
 pub use nms::NodeMediaServer;
 pub use obs::Obs;
+pub use push::Push;
+pub use recorder::Recorder;
+pub use replay::Replay;
 pub use rist::Rist;
 pub use sls::SrtLiveServer;
 pub use xiu::Xiu;
@@ -37,7 +50,17 @@ pub trait SwitchLogic {
 #[typetag::serde(tag = "type")]
 pub trait StreamServersCommands {
     async fn bitrate(&self) -> Bitrate;
-    async fn source_info(&self) -> Option<String>;
+    async fn source_info(&self) -> Option<SourceInfo>;
+
+    /// Video and audio details of the stream when the server reports them
+    async fn metadata(&self) -> Option<StreamMetadata> {
+        None
+    }
+
+    /// Current metrics of the stream, [`None`] when offline
+    async fn metrics(&self) -> Option<Metrics> {
+        None
+    }
 }
 
 #[typetag::serde(tag = "type")]
@@ -50,6 +73,214 @@ pub struct Bitrate {
     pub message: Option<String>,
 }
 
+/// Metrics reported by every kind of stream server
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+    /// Bitrate in Kbps
+    pub bitrate: u32,
+
+    /// Round trip time in ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt: Option<f64>,
+
+    /// Lost packets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loss: Option<u64>,
+}
+
+impl Metrics {
+    /// Which scene to switch to based on the metrics
+    pub fn switch_type(&self, triggers: &switcher::Triggers) -> switcher::SwitchType {
+        use switcher::SwitchType;
+
+        let rtt = self.rtt.unwrap_or_default();
+
+        if let Some(offline) = triggers.offline {
+            if self.bitrate > 0 && self.bitrate <= offline {
+                return SwitchType::Offline;
+            }
+        }
+
+        if let Some(rtt_offline) = triggers.rtt_offline {
+            if rtt >= rtt_offline.into() {
+                return SwitchType::Offline;
+            }
+        }
+
+        if self.bitrate == 0 {
+            return SwitchType::Previous;
+        }
+
+        if let Some(low) = triggers.low {
+            if self.bitrate <= low {
+                return SwitchType::Low;
+            }
+        }
+
+        if let Some(rtt_trigger) = triggers.rtt {
+            if rtt >= rtt_trigger.into() {
+                return SwitchType::Low;
+            }
+        }
+
+        SwitchType::Normal
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamMetadata {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub video_codec: Option<String>,
+    pub video_profile: Option<String>,
+    pub video_level: Option<f64>,
+    pub audio_codec: Option<String>,
+    pub audio_profile: Option<String>,
+    pub audio_channels: Option<u32>,
+    pub audio_sample_rate: Option<u32>,
+}
+
+impl StreamMetadata {
+    pub fn has_audio(&self) -> bool {
+        self.audio_codec.is_some() || self.audio_channels.is_some_and(|c| c > 0)
+    }
+}
+
+/// Details about the source of a stream server, the chat renders every
+/// field with the `sourceinfo.fields.<kind>` template
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct SourceInfo {
+    pub fields: Vec<SourceInfoField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceInfoField {
+    pub kind: &'static str,
+    pub values: BTreeMap<&'static str, SourceInfoValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SourceInfoValue {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl SourceInfo {
+    /// Adds a field with a single value named after the kind
+    pub fn with(self, kind: &'static str, value: impl Into<SourceInfoValue>) -> Self {
+        self.with_values(kind, [(kind, value.into())])
+    }
+
+    /// Adds a field with multiple named values
+    pub fn with_values<I>(mut self, kind: &'static str, values: I) -> Self
+    where
+        I: IntoIterator<Item = (&'static str, SourceInfoValue)>,
+    {
+        let values = values.into_iter().collect();
+
+        self.fields.push(SourceInfoField { kind, values });
+        self
+    }
+
+    /// Adds a field with a single value when there is one
+    pub fn with_opt<V: Into<SourceInfoValue>>(self, kind: &'static str, value: Option<V>) -> Self {
+        match value {
+            Some(value) => self.with(kind, value),
+            None => self,
+        }
+    }
+}
+
+impl SourceInfo {
+    /// Source info of a stream server that reports the stream metadata,
+    /// missing metadata is left out
+    pub fn from_metadata(bitrate: impl Into<SourceInfoValue>, meta: StreamMetadata) -> Self {
+        fn present(
+            values: Vec<(&'static str, Option<SourceInfoValue>)>,
+        ) -> impl Iterator<Item = (&'static str, SourceInfoValue)> {
+            values
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value?)))
+        }
+
+        let video = present(vec![
+            ("height", meta.height.map(Into::into)),
+            ("frameRate", meta.frame_rate.map(Into::into)),
+            ("codec", meta.video_codec.map(Into::into)),
+            ("profile", meta.video_profile.map(Into::into)),
+            ("level", meta.video_level.map(Into::into)),
+        ]);
+
+        let audio = present(vec![
+            ("codec", meta.audio_codec.map(Into::into)),
+            ("profile", meta.audio_profile.map(Into::into)),
+            ("sampleRate", meta.audio_sample_rate.map(Into::into)),
+            ("channels", meta.audio_channels.map(Into::into)),
+        ]);
+
+        Self::default()
+            .with("bitrate", bitrate)
+            .with_values("video", video)
+            .with_values("audio", audio)
+    }
+}
+
+impl From<Metrics> for SourceInfo {
+    fn from(metrics: Metrics) -> Self {
+        Self::default()
+            .with("bitrate", metrics.bitrate)
+            .with_opt("rtt", metrics.rtt)
+            .with_opt("lost", metrics.loss)
+    }
+}
+
+macro_rules! impl_source_info_value {
+    ($variant:ident, $as:ty, $($from:ty),+) => {
+        $(
+            impl From<$from> for SourceInfoValue {
+                fn from(value: $from) -> Self {
+                    Self::$variant(value as $as)
+                }
+            }
+        )+
+    };
+}
+
+impl_source_info_value!(Integer, i64, u8, u32, u64, i32, i64, usize);
+impl_source_info_value!(Float, f64, f32, f64);
+
+impl From<String> for SourceInfoValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<&str> for SourceInfoValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_owned())
+    }
+}
+
+impl std::fmt::Display for SourceInfoValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Float(n) => {
+                // At most two decimals without trailing zeros
+                let n = format!("{:.2}", n);
+                write!(f, "{}", n.trim_end_matches('0').trim_end_matches('.'))
+            }
+            Self::Text(t) => write!(f, "{}", t),
+        }
+    }
+}
+
 // TODO: This needs a better name
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -73,6 +304,25 @@ pub struct StreamServer {
     pub enabled: bool,
 }
 
+/// Summary of a stream server
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamServerInfo {
+    pub name: String,
+    pub priority: Option<i32>,
+    pub enabled: bool,
+}
+
+impl From<&StreamServer> for StreamServerInfo {
+    fn from(server: &StreamServer) -> Self {
+        Self {
+            name: server.name.to_owned(),
+            priority: server.priority,
+            enabled: server.enabled,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DependsOn {
@@ -83,12 +333,3 @@ pub struct DependsOn {
 fn default_server_enabled() -> bool {
     true
 }
-
-static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
-
-fn default_reqwest_client() -> reqwest::Client {
-    reqwest::Client::builder()
-        .user_agent(APP_USER_AGENT)
-        .build()
-        .expect("Failed to create reqwest client")
-}
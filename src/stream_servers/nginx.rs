@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, StreamMetadata, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -26,7 +26,7 @@ struct NginxRtmpLive {
     stream: Option<Vec<NginxRtmpStream>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct NginxRtmpStream {
     pub name: String,
     pub bw_video: u32,
@@ -34,13 +34,13 @@ pub struct NginxRtmpStream {
     pub active: Option<()>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Meta {
     video: Video,
     audio: Audio,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 pub struct Video {
     width: Option<u32>,
@@ -52,7 +52,7 @@ pub struct Video {
     level: Option<f64>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Audio {
     codec: Option<String>,
     profile: Option<String>,
@@ -60,6 +60,25 @@ pub struct Audio {
     sample_rate: Option<u32>,
 }
 
+impl From<Meta> for StreamMetadata {
+    fn from(meta: Meta) -> Self {
+        let Meta { video, audio } = meta;
+
+        Self {
+            width: video.width,
+            height: video.height,
+            frame_rate: video.frame_rate,
+            video_codec: video.codec,
+            video_profile: video.profile,
+            video_level: video.level,
+            audio_codec: audio.codec,
+            audio_profile: audio.profile,
+            audio_channels: audio.channels,
+            audio_sample_rate: audio.sample_rate,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Nginx {
@@ -72,15 +91,26 @@ pub struct Nginx {
     /// Stream key
     pub key: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<NginxRtmpStream>,
 }
 
 impl Nginx {
     /// 0 bitrate means the stream just started.
     /// the stats update every 10 seconds.
     pub async fn get_stats(&self) -> Option<NginxRtmpStream> {
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
+
+    async fn fetch_stats(&self) -> Option<NginxRtmpStream> {
         //TODO: keep the reqwest object around for future requests
         let res = match self.client.get(&self.stats_url).send().await {
             Ok(res) => res,
@@ -109,12 +139,9 @@ impl Nginx {
             .server
             .application
             .into_iter()
-            .filter_map(|x| {
-                if x.live.is_some() && x.name == self.application {
-                    x.live.unwrap().stream
-                } else {
-                    None
-                }
+            .filter_map(|x| match x.live {
+                Some(live) if x.name == self.application => live.stream,
+                _ => None,
             })
             .flatten()
             .filter(|x| x.name == self.key)
@@ -177,32 +204,30 @@ impl StreamServersCommands for Nginx {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
-        let meta = stats.meta?;
-        let video = meta.video;
-        let audio = meta.audio;
-
-        let bitrate = format!("{} Kbps", stats.bw_video / 1024);
-
-        let v_info = format!(
-            "{}p{} | {} {} {}",
-            video.height.unwrap_or_default(),
-            video.frame_rate.unwrap_or_default(),
-            video.codec.unwrap_or_default(),
-            video.profile.unwrap_or_default(),
-            video.level.unwrap_or_default()
-        );
+        let meta = StreamMetadata::from(stats.meta?);
 
-        let a_info = format!(
-            "{} {} {}Hz, {} channels",
-            audio.codec.unwrap_or_default(),
-            audio.profile.unwrap_or_default(),
-            audio.sample_rate.unwrap_or_default(),
-            audio.channels.unwrap_or_default(),
-        );
+        Some(super::SourceInfo::from_metadata(
+            stats.bw_video / 1024,
+            meta,
+        ))
+    }
+
+    async fn metadata(&self) -> Option<StreamMetadata> {
+        let stats = self.get_stats().await?;
 
-        Some(format!("{} | {} | {}", bitrate, v_info, a_info))
+        stats.meta.map(StreamMetadata::from)
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
+        stats.active?;
+
+        Some(super::Metrics {
+            bitrate: stats.bw_video / 1024,
+            ..Default::default()
+        })
     }
 }
 
@@ -302,6 +327,45 @@ mod tests {
         println!("{:#?}", parsed);
     }
 
+    #[test]
+    fn stream_metadata() {
+        let text = r#"
+            <meta>
+                <video>
+                    <width>1280</width>
+                    <height>720</height>
+                    <frame_rate>30.000</frame_rate>
+                    <codec>H264</codec>
+                    <profile>High</profile>
+                    <compat>0</compat>
+                    <level>3.1</level>
+                </video>
+                <audio></audio>
+            </meta>
+        "#;
+
+        let meta: Meta = quick_xml::de::from_str(text).unwrap();
+        let metadata = StreamMetadata::from(meta);
+
+        assert_eq!(metadata.height, Some(720));
+        assert_eq!(metadata.video_codec.as_deref(), Some("H264"));
+        assert!(!metadata.has_audio());
+
+        let triggers = crate::switcher::FormatTriggers {
+            min_height: Some(1080),
+            require_audio: true,
+            switch_to_low: false,
+        };
+
+        assert_eq!(
+            triggers.check(&metadata),
+            Some(crate::switcher::FormatIssue::LowResolution {
+                height: 720,
+                min_height: 1080
+            })
+        );
+    }
+
     #[test]
     fn application_without_live() {
         let text = r#"
@@ -1,8 +1,10 @@
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, trace};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -76,9 +78,72 @@ pub struct Streams {
     pub strm: String,
 }
 
+#[derive(Clone)]
 pub struct Stat {
     pub srt: SrtReceiver,
-    pub rtmp: Streams,
+    pub rtmp: Option<Streams>,
+
+    /// Bitrate in Kbps
+    pub bitrate: u32,
+}
+
+/// Where the bitrate gets read from
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateSource {
+    /// The outgoing RTMP stream, requires Nimble to republish the SRT stream
+    #[default]
+    Rtmp,
+
+    /// The byte counters of the SRT receiver
+    Srt,
+}
+
+impl BitrateSource {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Last byte counter of the SRT receiver, used to calculate the bitrate
+#[derive(Debug, Default)]
+pub struct SrtCounter {
+    /// Stats time in milliseconds and bytes received of the last sample
+    last: Option<(u64, u64)>,
+
+    /// Bitrate in Kbps
+    bitrate: Option<u32>,
+}
+
+impl SrtCounter {
+    /// Bitrate in Kbps since the previous sample
+    fn update(&mut self, time: u64, bytes: u64) -> Option<u32> {
+        // Same sample as last time
+        if self.last == Some((time, bytes)) {
+            return self.bitrate;
+        }
+
+        self.bitrate = match self.last {
+            Some((last_time, last_bytes)) if time > last_time && bytes >= last_bytes => {
+                let bits = (bytes - last_bytes) * 8;
+                let kbps = bits * 1000 / (time - last_time) / 1024;
+
+                Some(kbps.try_into().unwrap_or(u32::MAX))
+            }
+            // First sample or the counters got reset
+            _ => None,
+        };
+
+        self.last = Some((time, bytes));
+        self.bitrate
+    }
+}
+
+fn parse_bandwidth(bandwidth: &str) -> Result<u32, String> {
+    bandwidth
+        .trim()
+        .parse::<u64>()
+        .map(|b| (b / 1024).try_into().unwrap_or(u32::MAX))
+        .map_err(|e| format!("invalid bandwidth \"{}\" ({})", bandwidth, e))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -91,74 +156,114 @@ pub struct Nimble {
     pub stats_url: String,
 
     /// Outgoing stream "Application Name"
+    #[serde(default)]
     pub application: String,
 
     /// Outgoing stream "Stream Name"
+    #[serde(default)]
     pub key: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// Read the bitrate from the outgoing RTMP stream or the SRT receiver
+    #[serde(default, skip_serializing_if = "BitrateSource::is_default")]
+    pub bitrate_source: BitrateSource,
+
+    #[serde(skip)]
+    pub counter: Arc<Mutex<SrtCounter>>,
+
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<Stat>,
 }
 
 impl Nimble {
-    pub async fn get_stats(&self) -> Option<Stat> {
-        let url = format!("{}/manage/srt_receiver_stats", &self.stats_url);
-
-        let res = match self.client.get(&url).send().await {
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Option<T> {
+        let res = match self.client.get(url).send().await {
             Ok(res) => res,
-            Err(_) => {
-                error!("Stats page ({}) is unreachable", self.stats_url);
+            Err(e) => {
+                error!("Stats page ({}) is unreachable ({})", url, e);
                 return None;
             }
         };
 
         if res.status() != reqwest::StatusCode::OK {
-            error!("Error accessing stats page ({})", self.stats_url);
+            error!("Error accessing stats page ({})", url);
             return None;
         }
 
-        let text = res.text().await.ok()?;
-        let srt_stats: NimbleSrtStats = serde_json::from_str(&text).ok()?;
+        match res.json::<T>().await {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                error!("Error parsing stats ({}) {}", url, e);
+                None
+            }
+        }
+    }
+
+    pub async fn get_stats(&self) -> Option<Stat> {
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
+
+    async fn fetch_stats(&self) -> Option<Stat> {
+        let url = format!("{}/manage/srt_receiver_stats", &self.stats_url);
+        let srt_stats: NimbleSrtStats = self.get_json(&url).await?;
 
         let srt_receiver = srt_stats
             .srt_receivers
-            .iter()
+            .into_iter()
             .find(|x| x.id.contains(&self.id))?;
 
         if srt_receiver.state == "disconnected" {
+            *self.counter.lock().unwrap() = SrtCounter::default();
             return None;
         }
 
+        trace!("{:#?}", srt_receiver);
+
+        if self.bitrate_source == BitrateSource::Srt {
+            let recv = &srt_receiver.stats.recv;
+            let bitrate = self
+                .counter
+                .lock()
+                .unwrap()
+                .update(srt_receiver.stats.time, recv.bytes_received)
+                // Averaged rate until there are two samples to compare
+                .unwrap_or((recv.mbps_rate * 1000.0) as u32);
+
+            return Some(Stat {
+                srt: srt_receiver,
+                rtmp: None,
+                bitrate,
+            });
+        }
+
         // RTMP status for bitrate. srt_receiver_stats seems to give an averaged number that isn't as useful.
         // Probably requires nimble to be configured to make the video from SRT available on RTMP even though it's not used anywhere
         let url = format!("{}/manage/rtmp_status", &self.stats_url);
+        let rtmp_stats: Vec<NimbleRtmpStats> = self.get_json(&url).await?;
 
-        let res = match reqwest::get(&url).await {
-            Ok(res) => res,
-            Err(_) => {
-                error!("Stats page ({}) is unreachable", self.stats_url);
+        let rtmp_app = rtmp_stats.into_iter().find(|x| x.app == self.application)?;
+        let rtmp_stream = rtmp_app.streams.into_iter().find(|x| x.strm == self.key)?;
+
+        let bitrate = match parse_bandwidth(&rtmp_stream.bandwidth) {
+            Ok(bitrate) => bitrate,
+            Err(e) => {
+                error!("Error parsing stats ({}) {}", url, e);
                 return None;
             }
         };
 
-        if res.status() != reqwest::StatusCode::OK {
-            error!("Error accessing stats page ({})", self.stats_url);
-            return None;
-        }
-
-        let text = res.text().await.ok()?;
-        let rtmp_stats: Vec<NimbleRtmpStats> = serde_json::from_str(&text).ok()?;
-
-        let rtmp_app = rtmp_stats.iter().find(|x| x.app == self.application)?;
-        let rtmp_stream = rtmp_app.streams.iter().find(|x| x.strm == self.key)?;
-
-        let stat = Stat {
-            srt: srt_receiver.to_owned(),
-            rtmp: rtmp_stream.to_owned(),
-        };
-
-        Some(stat)
+        Some(Stat {
+            srt: srt_receiver,
+            rtmp: Some(rtmp_stream),
+            bitrate,
+        })
     }
 }
 
@@ -171,8 +276,7 @@ impl SwitchLogic for Nimble {
             None => return SwitchType::Offline,
         };
 
-        let bitrate = stats.rtmp.bandwidth.parse::<u32>().unwrap();
-        let bitrate = bitrate / 1024;
+        let bitrate = stats.bitrate;
 
         if let Some(offline) = triggers.offline {
             if bitrate > 0 && bitrate <= offline {
@@ -215,17 +319,33 @@ impl StreamServersCommands for Nimble {
             None => return super::Bitrate { message: None },
         };
 
-        let bitrate = stats.rtmp.bandwidth.parse::<u32>().unwrap();
-        let bitrate = bitrate / 1024;
-
-        let message = format!("{}, {} ms", bitrate, stats.srt.stats.link.rtt.round());
+        let message = format!("{}, {} ms", stats.bitrate, stats.srt.stats.link.rtt.round());
         super::Bitrate {
             message: Some(message),
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
-        self.bitrate().await.message
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        let stats = self.get_stats().await?;
+        let recv = &stats.srt.stats.recv;
+
+        let info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with("rtt", stats.srt.stats.link.rtt)
+            .with("lost", recv.packets_lost)
+            .with("dropped", recv.packets_dropped);
+
+        Some(info)
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
+
+        Some(super::Metrics {
+            bitrate: stats.bitrate,
+            rtt: Some(stats.srt.stats.link.rtt),
+            loss: Some(stats.srt.stats.recv.packets_lost),
+        })
     }
 }
 
@@ -235,3 +355,29 @@ impl Bsl for Nimble {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bandwidth() {
+        assert_eq!(parse_bandwidth("6291456"), Ok(6144));
+        assert!(parse_bandwidth("").is_err());
+        assert!(parse_bandwidth("6.1 Mbps").is_err());
+    }
+
+    #[test]
+    fn srt_byte_counter() {
+        let mut counter = SrtCounter::default();
+
+        assert_eq!(counter.update(1000, 0), None);
+        // 768000 bytes in 1 second
+        assert_eq!(counter.update(2000, 768_000), Some(6000));
+        // Polled again before the stats changed
+        assert_eq!(counter.update(2000, 768_000), Some(6000));
+        assert_eq!(counter.update(4000, 1_152_000), Some(1500));
+        // Receiver restarted
+        assert_eq!(counter.update(500, 1000), None);
+    }
+}
@@ -2,10 +2,12 @@ use async_trait::async_trait;
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{
+    http::LegacyAuth, stats_cache, Bsl, HttpClient, HttpOptions, StreamServersCommands, SwitchLogic,
+};
 use crate::switcher::{SwitchType, Triggers};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Stat {
     pub is_live: bool,
@@ -15,13 +17,7 @@ pub struct Stat {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Auth {
-    username: String,
-    password: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", try_from = "NodeMediaServerConfig")]
 pub struct NodeMediaServer {
     /// Url to the NGINX stats page
     pub stats_url: String,
@@ -32,25 +28,56 @@ pub struct NodeMediaServer {
     /// Stream key
     pub key: String,
 
-    pub auth: Option<Auth>,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<Stat>,
+}
+
+/// Node-Media-Server as written in the config, which can still have
+/// the old `auth` field
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeMediaServerConfig {
+    stats_url: String,
+    application: String,
+    key: String,
+    auth: Option<LegacyAuth>,
+    #[serde(default)]
+    http: HttpOptions,
+}
+
+impl TryFrom<NodeMediaServerConfig> for NodeMediaServer {
+    type Error = String;
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    fn try_from(config: NodeMediaServerConfig) -> Result<Self, Self::Error> {
+        let options = config.http.with_legacy_auth(config.auth)?;
+
+        Ok(Self {
+            stats_url: config.stats_url,
+            application: config.application,
+            key: config.key,
+            client: HttpClient::new(options)?,
+            recent: Default::default(),
+        })
+    }
 }
 
 impl NodeMediaServer {
     pub async fn get_stats(&self) -> Option<Stat> {
-        let url = format!("{}/{}/{}", &self.stats_url, &self.application, &self.key);
-
-        let client = &self.client;
-        let mut request = client.get(url);
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
 
-        if let Some(auth) = &self.auth {
-            request = request.basic_auth(&auth.username, Some(&auth.password));
-        }
+    async fn fetch_stats(&self) -> Option<Stat> {
+        let url = format!("{}/{}/{}", &self.stats_url, &self.application, &self.key);
 
-        let res = match request.send().await {
+        let res = match self.client.get(url).send().await {
             Ok(res) => res,
             Err(_) => {
                 error!("Stats page ({}) is unreachable", self.stats_url);
@@ -123,10 +150,23 @@ impl StreamServersCommands for NodeMediaServer {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
 
-        Some(format!("{} Kbps", stats.bitrate))
+        Some(super::SourceInfo::default().with("bitrate", stats.bitrate))
+    }
+
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
+
+        if !stats.is_live {
+            return None;
+        }
+
+        Some(super::Metrics {
+            bitrate: stats.bitrate.try_into().unwrap_or(u32::MAX),
+            ..Default::default()
+        })
     }
 }
 
@@ -136,3 +176,24 @@ impl Bsl for NodeMediaServer {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_auth_in_http_options() {
+        let json = r#"{"statsUrl":"http://localhost:8000/api/streams","application":"publish","key":"live","auth":{"username":"admin","password":"admin"}}"#;
+        let server: NodeMediaServer = serde_json::from_str(json).unwrap();
+
+        let request = server.client.get(&server.stats_url).build().unwrap();
+        assert_eq!(request.headers().get_all("authorization").iter().count(), 1);
+        assert_eq!(
+            serde_json::to_string(&server).unwrap(),
+            r#"{"statsUrl":"http://localhost:8000/api/streams","application":"publish","key":"live","http":{"auth":{"type":"basic","username":"admin","password":"admin"}}}"#
+        );
+
+        let json = r#"{"statsUrl":"http://localhost:8000/api/streams","application":"publish","key":"live","auth":{"username":"admin","password":"admin"},"http":{"auth":{"type":"bearer","token":"secret"}}}"#;
+        assert!(serde_json::from_str::<NodeMediaServer>(json).is_err());
+    }
+}
@@ -1,14 +1,35 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
 use async_trait::async_trait;
-use obws::responses::MediaState;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use super::{Bsl, StreamServersCommands, SwitchLogic};
 use crate::{
+    broadcasting_software::{MediaState, MediaStatus},
     noalbs,
     switcher::{self, SwitchType, Triggers},
 };
 
+/// Milliseconds the media source needs to be playing before it's used
+const STARTUP_CURSOR: i64 = 3000;
+
+/// Time span used to calculate the playback speed
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Seconds the cursor can stand still before the source is considered offline
+fn default_stall_timeout() -> u64 {
+    3
+}
+
+/// Decides on the playback of an OBS media source for setups where the
+/// stream goes straight into OBS. OBS WebSocket has no stats for a single
+/// input, v5 only reports the media state and cursor, so there's no bitrate
+/// to use the low and offline triggers on.
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Obs {
@@ -20,12 +41,99 @@ pub struct Obs {
 
     /// The name of the OBS media source
     pub source: String,
+
+    /// Seconds the media cursor can stand still while playing before the
+    /// source is considered offline
+    #[serde(default = "default_stall_timeout")]
+    pub stall_timeout: u64,
+
+    /// Switch to the low scene when the playback speed drops below this
+    /// (ex; 0.9 )
+    pub low_speed: Option<f64>,
+
+    #[serde(skip)]
+    pub cursor: Arc<Mutex<CursorTracker>>,
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    pub state: MediaState,
+
+    /// Position of the media cursor in milliseconds
+    pub cursor: i64,
+
+    /// Playback speed compared to real time
+    pub speed: Option<f64>,
+
+    /// How long the cursor hasn't moved
+    pub stalled: Duration,
+}
+
+/// Keeps track of the media cursor to detect a frozen or slow source
+#[derive(Default)]
+pub struct CursorTracker {
+    // Time and cursor position of the recent samples
+    samples: VecDeque<(Instant, i64)>,
+
+    // Last time the cursor moved
+    last_advanced: Option<Instant>,
+}
+
+impl CursorTracker {
+    fn update(&mut self, now: Instant, cursor: i64) {
+        if let Some(&(_, last)) = self.samples.back() {
+            // The media restarted
+            if cursor < last {
+                self.samples.clear();
+                self.last_advanced = None;
+            }
+        }
+
+        if self.samples.back().is_none_or(|&(_, last)| cursor > last) {
+            self.last_advanced = Some(now);
+        }
+
+        self.samples.push_back((now, cursor));
+
+        while let Some(&(time, _)) = self.samples.front() {
+            if now.duration_since(time) <= SPEED_WINDOW {
+                break;
+            }
+
+            self.samples.pop_front();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+        self.last_advanced = None;
+    }
+
+    /// Cursor progression compared to real time over the window
+    fn speed(&self) -> Option<f64> {
+        let (first_time, first_cursor) = self.samples.front()?;
+        let (last_time, last_cursor) = self.samples.back()?;
+
+        let elapsed = last_time.duration_since(*first_time).as_millis();
+
+        // Not enough samples yet
+        if elapsed < 1000 {
+            return None;
+        }
+
+        Some((last_cursor - first_cursor) as f64 / elapsed as f64)
+    }
+
+    fn stalled(&self, now: Instant) -> Duration {
+        self.last_advanced
+            .map_or(Duration::ZERO, |l| now.duration_since(l))
+    }
 }
 
 impl Obs {
-    pub async fn get_stats(&self) -> Option<(MediaState, i64)> {
+    pub async fn get_stats(&self) -> Option<Stats> {
         let state = self.state().read().await;
-        let bsc = state.broadcasting_software.connection.as_ref().unwrap();
+        let bsc = state.broadcasting_software.connection.as_ref()?;
 
         let current_scene = &state.broadcasting_software.current_scene;
         let offline_scene = match &self.scenes {
@@ -33,13 +141,16 @@ impl Obs {
             None => &state.config.switcher.switching_scenes.offline,
         };
 
+        // OBS v4 can't get the status of a source that isn't shown, so a
+        // duplicate gets added to the offline scene.
         let mut name = self.source.to_owned();
-        if current_scene == offline_scene {
-            name += "_noalbs";
-
-            let _ = bsc
+        if current_scene == offline_scene
+            && bsc
                 .create_special_media_source(&self.source, offline_scene)
-                .await;
+                .await
+                .is_ok()
+        {
+            name += "_noalbs";
 
             // TODO: Should the media source be removed?
             // dbg!(bsc.remove_media_source(&name.unwrap(), o).await);
@@ -48,28 +159,71 @@ impl Obs {
         let status = bsc.get_media_source_status(&name).await.ok();
         debug!("Media source status: {:?}", status);
 
-        status
+        let mut tracker = self.cursor.lock().unwrap();
+        let now = Instant::now();
+
+        let Some(MediaStatus { state, cursor }) = status else {
+            tracker.reset();
+            return None;
+        };
+
+        if state != MediaState::Playing {
+            tracker.reset();
+            return Some(Stats {
+                state,
+                cursor,
+                speed: None,
+                stalled: Duration::ZERO,
+            });
+        }
+
+        tracker.update(now, cursor);
+
+        let stats = Stats {
+            state,
+            cursor,
+            speed: tracker.speed(),
+            stalled: tracker.stalled(now),
+        };
+
+        debug!("{:#?}", stats);
+        Some(stats)
     }
 
     pub fn state(&self) -> &noalbs::UserState {
         self.state.as_ref().unwrap()
     }
+
+    fn switch_type(&self, stats: &Stats) -> SwitchType {
+        if stats.state != MediaState::Playing || stats.cursor < STARTUP_CURSOR {
+            return SwitchType::Offline;
+        }
+
+        // Still says playing but the picture is frozen
+        if stats.stalled >= Duration::from_secs(self.stall_timeout) {
+            return SwitchType::Offline;
+        }
+
+        if let (Some(low), Some(speed)) = (self.low_speed, stats.speed) {
+            if speed < low {
+                return SwitchType::Low;
+            }
+        }
+
+        SwitchType::Normal
+    }
 }
 
 #[async_trait]
 #[typetag::serde]
 impl SwitchLogic for Obs {
     async fn switch(&self, _: &Triggers) -> SwitchType {
-        let (state, sec) = match self.get_stats().await {
+        let stats = match self.get_stats().await {
             Some(stats) => stats,
             None => return SwitchType::Offline,
         };
 
-        if matches!(state, MediaState::Playing) && sec >= 3 {
-            return SwitchType::Normal;
-        }
-
-        SwitchType::Offline
+        self.switch_type(&stats)
     }
 }
 
@@ -77,29 +231,42 @@ impl SwitchLogic for Obs {
 #[typetag::serde]
 impl StreamServersCommands for Obs {
     async fn bitrate(&self) -> super::Bitrate {
-        let (state, _) = match self.get_stats().await {
+        let stats = match self.get_stats().await {
             Some(stats) => stats,
             None => return super::Bitrate { message: None },
         };
 
-        if !matches!(state, MediaState::Playing) {
+        if self.switch_type(&stats) == SwitchType::Offline {
             return super::Bitrate { message: None };
         }
 
-        let message = format!("{:?}", state);
+        let message = match stats.speed {
+            Some(speed) => format!("{:?} {:.2}x", stats.state, speed),
+            None => format!("{:?}", stats.state),
+        };
+
         super::Bitrate {
             message: Some(message),
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
-        let (state, sec) = self.get_stats().await?;
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        let stats = self.get_stats().await?;
 
-        if !matches!(state, MediaState::Playing) {
+        if stats.state != MediaState::Playing {
             return None;
         }
 
-        Some(format!("{state:?}, {sec} seconds"))
+        let mut info = super::SourceInfo::default()
+            .with("mediaState", format!("{:?}", stats.state))
+            .with("position", stats.cursor / 1000)
+            .with_opt("speed", stats.speed);
+
+        if !stats.stalled.is_zero() {
+            info = info.with("frozen", stats.stalled.as_secs());
+        }
+
+        Some(info)
     }
 }
 
@@ -109,3 +276,69 @@ impl Bsl for Obs {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_speed() {
+        let start = Instant::now();
+        let mut tracker = CursorTracker::default();
+
+        for i in 0..=4 {
+            tracker.update(start + Duration::from_secs(i), i as i64 * 500);
+        }
+
+        assert_eq!(tracker.speed(), Some(0.5));
+        assert_eq!(
+            tracker.stalled(start + Duration::from_secs(4)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn frozen_cursor() {
+        let start = Instant::now();
+        let mut tracker = CursorTracker::default();
+
+        tracker.update(start, 10_000);
+        tracker.update(start + Duration::from_secs(1), 11_000);
+        tracker.update(start + Duration::from_secs(2), 11_000);
+        tracker.update(start + Duration::from_secs(4), 11_000);
+
+        let now = start + Duration::from_secs(4);
+        assert_eq!(tracker.stalled(now), Duration::from_secs(3));
+
+        // Restarting the media isn't a stall
+        tracker.update(now + Duration::from_secs(1), 100);
+        assert_eq!(
+            tracker.stalled(now + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+        assert_eq!(tracker.speed(), None);
+    }
+
+    #[test]
+    fn bitrate_triggers_rejected() {
+        let mut switcher: crate::config::Switcher = serde_json::from_str(
+            r#"{
+                "triggers": { "low": 800, "offline": null },
+                "streamServers": [{
+                    "streamServer": { "type": "Obs", "source": "SRT" },
+                    "name": "OBS",
+                    "priority": 0
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            switcher.check_bitrate_triggers(),
+            Err(crate::error::Error::BitrateTriggersWithObs)
+        ));
+
+        switcher.triggers.low = None;
+        assert!(switcher.check_bitrate_triggers().is_ok());
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use super::{Bsl, Metrics, StreamServersCommands, SwitchLogic};
+use crate::switcher::{SwitchType, Triggers};
+
+/// Push stream servers by id, shared between every user in the process
+static REGISTRY: Lazy<Mutex<HashMap<String, Slot>>> = Lazy::new(Default::default);
+
+/// Makes sure only the latest registration of an id can remove its slot
+static NEXT_REGISTRATION: AtomicU64 = AtomicU64::new(0);
+
+struct Slot {
+    /// Name of the user the id belongs to
+    owner: String,
+    registration: u64,
+    token: String,
+    sample: Option<(Instant, Metrics)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PushError {
+    /// No push stream server with this id
+    NotFound,
+
+    /// Missing or wrong token
+    Unauthorized,
+
+    /// The id is used by another user
+    IdTaken,
+}
+
+/// Stores the metrics pushed by a sender for the push stream server with the id
+pub fn push(id: &str, token: Option<&str>, metrics: Metrics) -> Result<(), PushError> {
+    let mut registry = REGISTRY.lock().unwrap();
+    let slot = registry.get_mut(id).ok_or(PushError::NotFound)?;
+
+    if !token.is_some_and(|t| tokens_match(t, &slot.token)) {
+        return Err(PushError::Unauthorized);
+    }
+
+    trace!("Pushed to {}: {:?}", id, metrics);
+    slot.sample = Some((Instant::now(), metrics));
+
+    Ok(())
+}
+
+/// Compares the tokens in constant time so the token can't be guessed
+/// by timing the responses
+fn tokens_match(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Seconds without a new sample before the stream is considered offline
+fn default_stale_timeout() -> u64 {
+    5
+}
+
+/// Uses the stats that get pushed to the web server instead of polling
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Push {
+    /// Id the stats get pushed to (ex; POST /push/bond )
+    pub id: String,
+
+    /// Bearer token the sender has to authenticate with
+    pub token: String,
+
+    /// Seconds without a new sample before the stream is considered offline
+    #[serde(default = "default_stale_timeout")]
+    pub stale_timeout: u64,
+
+    #[serde(skip)]
+    pub registration: Option<u64>,
+}
+
+impl Push {
+    /// Start accepting stats for this stream server of the user,
+    /// the id can't be used by another user
+    pub fn register(&mut self, owner: &str) -> Result<(), PushError> {
+        let mut registry = REGISTRY.lock().unwrap();
+        let registration = NEXT_REGISTRATION.fetch_add(1, Ordering::Relaxed);
+
+        match registry.get_mut(&self.id) {
+            Some(slot) if slot.owner != owner => return Err(PushError::IdTaken),
+            Some(slot) => {
+                if slot.token != self.token {
+                    slot.token = self.token.to_owned();
+                    slot.sample = None;
+                }
+
+                slot.registration = registration;
+            }
+            None => {
+                registry.insert(
+                    self.id.to_owned(),
+                    Slot {
+                        owner: owner.to_owned(),
+                        registration,
+                        token: self.token.to_owned(),
+                        sample: None,
+                    },
+                );
+            }
+        }
+
+        self.registration = Some(registration);
+
+        Ok(())
+    }
+
+    pub fn unregister(&mut self) {
+        let Some(registration) = self.registration.take() else {
+            return;
+        };
+
+        let mut registry = REGISTRY.lock().unwrap();
+
+        if registry
+            .get(&self.id)
+            .is_some_and(|slot| slot.registration == registration)
+        {
+            registry.remove(&self.id);
+        }
+    }
+
+    /// The latest pushed sample if it isn't stale
+    pub fn get_stats(&self) -> Option<Metrics> {
+        let registry = REGISTRY.lock().unwrap();
+        let slot = registry.get(&self.id)?;
+
+        if self.registration != Some(slot.registration) {
+            return None;
+        }
+
+        let (at, metrics) = slot.sample.as_ref()?;
+
+        if at.elapsed() >= Duration::from_secs(self.stale_timeout) {
+            return None;
+        }
+
+        Some(metrics.to_owned())
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl SwitchLogic for Push {
+    /// Which scene to switch to
+    async fn switch(&self, triggers: &Triggers) -> SwitchType {
+        match self.get_stats() {
+            Some(metrics) => metrics.switch_type(triggers),
+            None => SwitchType::Offline,
+        }
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl StreamServersCommands for Push {
+    async fn bitrate(&self) -> super::Bitrate {
+        let metrics = match self.get_stats() {
+            Some(metrics) => metrics,
+            None => return super::Bitrate { message: None },
+        };
+
+        let message = match metrics.rtt {
+            Some(rtt) => format!("{}, {} ms", metrics.bitrate, rtt.round()),
+            None => format!("{}", metrics.bitrate),
+        };
+
+        super::Bitrate {
+            message: Some(message),
+        }
+    }
+
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        self.get_stats().map(super::SourceInfo::from)
+    }
+
+    async fn metrics(&self) -> Option<Metrics> {
+        self.get_stats()
+    }
+}
+
+#[typetag::serde]
+impl Bsl for Push {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl Drop for Push {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(bitrate: u32) -> Metrics {
+        Metrics {
+            bitrate,
+            rtt: Some(80.0),
+            loss: None,
+        }
+    }
+
+    fn server(id: &str, token: &str, stale_timeout: u64) -> Push {
+        Push {
+            id: id.to_string(),
+            token: token.to_string(),
+            stale_timeout,
+            registration: None,
+        }
+    }
+
+    #[test]
+    fn push_and_read() {
+        let mut server = server("test-push", "secret", 5);
+
+        assert_eq!(
+            push("test-push", Some("secret"), metrics(1)),
+            Err(PushError::NotFound)
+        );
+
+        server.register("user").unwrap();
+        assert_eq!(server.get_stats(), None);
+        assert_eq!(
+            push("test-push", None, metrics(1)),
+            Err(PushError::Unauthorized)
+        );
+        assert_eq!(
+            push("test-push", Some("wrong"), metrics(1)),
+            Err(PushError::Unauthorized)
+        );
+
+        push("test-push", Some("secret"), metrics(6000)).unwrap();
+        assert_eq!(server.get_stats(), Some(metrics(6000)));
+
+        // Registering again keeps the latest sample
+        server.register("user").unwrap();
+        assert_eq!(server.get_stats(), Some(metrics(6000)));
+
+        server.unregister();
+        assert_eq!(server.get_stats(), None);
+    }
+
+    #[test]
+    fn id_of_another_user() {
+        let mut first = server("test-owner", "first", 5);
+        let mut second = server("test-owner", "second", 5);
+
+        first.register("first").unwrap();
+        assert_eq!(second.register("second"), Err(PushError::IdTaken));
+
+        push("test-owner", Some("first"), metrics(6000)).unwrap();
+        assert_eq!(second.get_stats(), None);
+
+        // Only the owner can remove the slot
+        drop(second);
+        assert_eq!(first.get_stats(), Some(metrics(6000)));
+
+        drop(first);
+        assert_eq!(
+            push("test-owner", Some("first"), metrics(1)),
+            Err(PushError::NotFound)
+        );
+    }
+
+    #[test]
+    fn stale_sample() {
+        let mut server = server("test-stale", "secret", 0);
+
+        server.register("user").unwrap();
+        push("test-stale", Some("secret"), metrics(6000)).unwrap();
+
+        assert_eq!(server.get_stats(), None);
+    }
+
+    #[test]
+    fn compare_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secret1"));
+    }
+}
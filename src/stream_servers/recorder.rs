@@ -0,0 +1,163 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::error;
+
+use super::{
+    replay::TraceSample, Bsl, Metrics, StreamMetadata, StreamServersCommands, SwitchLogic,
+};
+use crate::switcher::{SwitchType, Triggers};
+
+/// Records the metrics of a stream server to a JSONL trace that can be
+/// played back with [`super::Replay`]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recorder {
+    /// Path to the JSONL trace, new samples get appended (ex; traces/irl.jsonl )
+    pub path: String,
+
+    /// The stream server to record
+    pub server: Box<dyn Bsl>,
+
+    #[serde(skip)]
+    pub started: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Recorder {
+    async fn record(&self, metrics: Option<Metrics>) {
+        let time = {
+            let mut started = self.started.lock().unwrap();
+            started.get_or_insert_with(Instant::now).elapsed()
+        };
+
+        let sample = TraceSample {
+            time: (time.as_secs_f64() * 1000.0).round() / 1000.0,
+            metrics,
+        };
+
+        let Ok(mut line) = serde_json::to_string(&sample) else {
+            return;
+        };
+        line.push('\n');
+
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await;
+
+        let res = match file {
+            Ok(mut file) => match file.write_all(line.as_bytes()).await {
+                // Tokio only finishes the write in the background otherwise
+                Ok(()) => file.flush().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = res {
+            error!("Unable to write trace ({}) {}", self.path, e);
+        }
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl SwitchLogic for Recorder {
+    /// Which scene to switch to, the wrapped server decides
+    async fn switch(&self, triggers: &Triggers) -> SwitchType {
+        let switch_type = self.server.switch(triggers).await;
+
+        if switch_type == SwitchType::Offline {
+            self.record(None).await;
+            return switch_type;
+        }
+
+        // Stream servers reuse the stats they just fetched for the switch,
+        // servers without metrics (like Obs) only get their offline moments recorded
+        if let Some(metrics) = self.server.metrics().await {
+            self.record(Some(metrics)).await;
+        }
+
+        switch_type
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl StreamServersCommands for Recorder {
+    async fn bitrate(&self) -> super::Bitrate {
+        self.server.bitrate().await
+    }
+
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        self.server.source_info().await
+    }
+
+    async fn metadata(&self) -> Option<StreamMetadata> {
+        self.server.metadata().await
+    }
+
+    async fn metrics(&self) -> Option<Metrics> {
+        self.server.metrics().await
+    }
+}
+
+#[typetag::serde]
+impl Bsl for Recorder {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_servers::{replay::Trace, Replay};
+
+    #[tokio::test]
+    async fn records_replayable_trace() {
+        let dir = std::env::temp_dir().join(format!("noalbs-recorder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let source = dir.join("source.csv");
+        std::fs::write(&source, "time,bitrate,rtt\n0,6000,40\n60,6000,40\n").unwrap();
+        let output = dir.join("output.jsonl");
+        let _ = std::fs::remove_file(&output);
+
+        let recorder = Recorder {
+            path: output.to_string_lossy().to_string(),
+            server: Box::new(Replay {
+                path: source.to_string_lossy().to_string(),
+                speed: 1.0,
+                looping: true,
+                player: Default::default(),
+            }),
+            started: Default::default(),
+        };
+
+        let triggers = Triggers::default();
+        assert_eq!(recorder.switch(&triggers).await, SwitchType::Normal);
+        assert_eq!(recorder.switch(&triggers).await, SwitchType::Normal);
+
+        let text = std::fs::read_to_string(&output).unwrap();
+        let trace = Trace::parse(&text).unwrap();
+
+        assert_eq!(trace.samples.len(), 2);
+        assert_eq!(
+            trace.samples[0].metrics,
+            Some(Metrics {
+                bitrate: 6000,
+                rtt: Some(40.0),
+                loss: None
+            })
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::{error, trace};
+
+use super::{Bsl, Metrics, StreamServersCommands, SwitchLogic};
+use crate::switcher::{SwitchType, Triggers};
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// Plays back a recorded trace of metrics
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    /// Path to a CSV or JSONL trace (ex; traces/irl.jsonl )
+    pub path: String,
+
+    /// Playback speed, 2.0 plays the trace twice as fast
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+
+    /// Start over when the end of the trace is reached
+    #[serde(default)]
+    pub looping: bool,
+
+    #[serde(skip)]
+    pub player: Arc<Mutex<Player>>,
+}
+
+#[derive(Default)]
+pub struct Player {
+    trace: Option<Trace>,
+    started: Option<Instant>,
+}
+
+/// A sample in a trace, a sample without a bitrate means the stream was offline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceSample {
+    /// Seconds since the start of the trace
+    pub time: f64,
+
+    #[serde(flatten)]
+    pub metrics: Option<Metrics>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Trace {
+    pub samples: Vec<TraceSample>,
+}
+
+impl Trace {
+    /// Parses a JSONL trace or a CSV trace with a `time,bitrate,rtt,loss` header
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty())
+            .peekable();
+
+        let is_jsonl = lines.peek().is_some_and(|(_, l)| l.starts_with('{'));
+
+        let mut samples = if is_jsonl {
+            lines
+                .map(|(i, line)| {
+                    serde_json::from_str::<TraceSample>(line)
+                        .map_err(|e| format!("line {}: {}", i, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Self::parse_csv(lines)?
+        };
+
+        samples.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self { samples })
+    }
+
+    fn parse_csv<'a>(
+        mut lines: impl Iterator<Item = (usize, &'a str)>,
+    ) -> Result<Vec<TraceSample>, String> {
+        let (_, header) = lines.next().ok_or("empty trace")?;
+        let columns = header.split(',').map(str::trim).collect::<Vec<_>>();
+        let column = |name: &str| columns.iter().position(|c| c.eq_ignore_ascii_case(name));
+
+        let time = column("time").ok_or("missing time column")?;
+        let bitrate = column("bitrate").ok_or("missing bitrate column")?;
+        let rtt = column("rtt");
+        let loss = column("loss");
+
+        lines
+            .map(|(i, line)| {
+                let values = line.split(',').map(str::trim).collect::<Vec<_>>();
+                let value = |index: Option<usize>| {
+                    index
+                        .and_then(|index| values.get(index))
+                        .filter(|v| !v.is_empty())
+                };
+                let error = |column: &str| format!("line {}: invalid {}", i, column);
+
+                let time = value(Some(time))
+                    .and_then(|v| v.parse::<f64>().ok())
+                    .ok_or_else(|| error("time"))?;
+
+                let metrics = match value(Some(bitrate)) {
+                    Some(bitrate) => Some(Metrics {
+                        bitrate: bitrate.parse().map_err(|_| error("bitrate"))?,
+                        rtt: value(rtt)
+                            .map(|v| v.parse())
+                            .transpose()
+                            .map_err(|_| error("rtt"))?,
+                        loss: value(loss)
+                            .map(|v| v.parse())
+                            .transpose()
+                            .map_err(|_| error("loss"))?,
+                    }),
+                    None => None,
+                };
+
+                Ok(TraceSample { time, metrics })
+            })
+            .collect()
+    }
+
+    /// Time of the last sample in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.last().map_or(0.0, |s| s.time)
+    }
+
+    /// The sample at the given position in seconds
+    pub fn sample_at(&self, position: f64, looping: bool) -> Option<&TraceSample> {
+        let duration = self.duration();
+
+        let position = if looping && duration > 0.0 {
+            position % duration
+        } else if position > duration {
+            return None;
+        } else {
+            position
+        };
+
+        self.samples
+            .iter()
+            .take_while(|s| s.time <= position)
+            .last()
+    }
+}
+
+impl Replay {
+    /// Metrics at the current point of the replay
+    pub async fn get_stats(&self) -> Option<Metrics> {
+        if self.player.lock().unwrap().trace.is_none() {
+            let trace = self.load().await?;
+            self.player.lock().unwrap().trace = Some(trace);
+        }
+
+        let mut player = self.player.lock().unwrap();
+        let started = *player.started.get_or_insert_with(Instant::now);
+
+        let metrics = self.metrics_at(&player, started.elapsed());
+        trace!("{:#?}", metrics);
+
+        metrics
+    }
+
+    /// Plays the trace as if the replay started `elapsed` ago
+    #[cfg(test)]
+    pub(crate) fn seek(&self, trace: Trace, elapsed: Duration) {
+        let mut player = self.player.lock().unwrap();
+        player.trace = Some(trace);
+        player.started = Instant::now().checked_sub(elapsed);
+    }
+
+    fn metrics_at(&self, player: &Player, elapsed: Duration) -> Option<Metrics> {
+        let position = elapsed.as_secs_f64() * self.speed;
+
+        player
+            .trace
+            .as_ref()?
+            .sample_at(position, self.looping)?
+            .metrics
+            .to_owned()
+    }
+
+    async fn load(&self) -> Option<Trace> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(e) => {
+                error!("Unable to read trace ({}) {}", self.path, e);
+                return None;
+            }
+        };
+
+        match Trace::parse(&text) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                error!("Error parsing trace ({}) {}", self.path, e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl SwitchLogic for Replay {
+    /// Which scene to switch to
+    async fn switch(&self, triggers: &Triggers) -> SwitchType {
+        match self.get_stats().await {
+            Some(metrics) => metrics.switch_type(triggers),
+            None => SwitchType::Offline,
+        }
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl StreamServersCommands for Replay {
+    async fn bitrate(&self) -> super::Bitrate {
+        let metrics = match self.get_stats().await {
+            Some(metrics) => metrics,
+            None => return super::Bitrate { message: None },
+        };
+
+        let message = match metrics.rtt {
+            Some(rtt) => format!("{}, {} ms", metrics.bitrate, rtt.round()),
+            None => format!("{}", metrics.bitrate),
+        };
+
+        super::Bitrate {
+            message: Some(message),
+        }
+    }
+
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        self.get_stats().await.map(super::SourceInfo::from)
+    }
+
+    async fn metrics(&self) -> Option<Metrics> {
+        self.get_stats().await
+    }
+}
+
+#[typetag::serde]
+impl Bsl for Replay {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(text: &str, speed: f64, looping: bool) -> (Replay, Player) {
+        let replay = Replay {
+            path: String::new(),
+            speed,
+            looping,
+            player: Default::default(),
+        };
+
+        let player = Player {
+            trace: Some(Trace::parse(text).unwrap()),
+            started: None,
+        };
+
+        (replay, player)
+    }
+
+    #[test]
+    fn parse_jsonl() {
+        let text = r#"{"time":0.0,"bitrate":6000,"rtt":40.0}
+{"time":1.0}
+{"time":2.0,"bitrate":300,"rtt":900.0,"loss":12}
+"#;
+
+        let trace = Trace::parse(text).unwrap();
+
+        assert_eq!(trace.samples.len(), 3);
+        assert_eq!(trace.samples[1].metrics, None);
+        assert_eq!(
+            trace.samples[2].metrics,
+            Some(Metrics {
+                bitrate: 300,
+                rtt: Some(900.0),
+                loss: Some(12)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_csv() {
+        let text = "time,bitrate,rtt,loss
+0,6000,40,0
+1,,,
+2,300,900,12
+";
+
+        let csv = Trace::parse(text).unwrap();
+        let jsonl = Trace::parse(
+            r#"{"time":0.0,"bitrate":6000,"rtt":40.0,"loss":0}
+{"time":1.0}
+{"time":2.0,"bitrate":300,"rtt":900.0,"loss":12}"#,
+        )
+        .unwrap();
+
+        assert_eq!(csv, jsonl);
+        assert!(Trace::parse("time,bitrate\n0,abc").is_err());
+    }
+
+    #[test]
+    fn switching_during_replay() {
+        let text = "time,bitrate,rtt
+0,6000,40
+10,500,60
+20,,
+30,6000,2600
+";
+
+        let triggers = Triggers::default();
+        let (replay, player) = replay(text, 2.0, false);
+
+        let switch_type = |secs: u64| {
+            replay
+                .metrics_at(&player, Duration::from_secs(secs))
+                .map_or(SwitchType::Offline, |m| m.switch_type(&triggers))
+        };
+
+        // Twice as fast so every 5 seconds is a new sample
+        assert_eq!(switch_type(0), SwitchType::Normal);
+        assert_eq!(switch_type(5), SwitchType::Low);
+        assert_eq!(switch_type(10), SwitchType::Offline);
+        assert_eq!(switch_type(15), SwitchType::Low);
+        assert_eq!(switch_type(16), SwitchType::Offline);
+    }
+
+    #[test]
+    fn looping_replay() {
+        let text = "time,bitrate\n0,6000\n10,500\n20,6000\n";
+        let (replay, player) = replay(text, 1.0, true);
+
+        let bitrate = |secs: u64| {
+            replay
+                .metrics_at(&player, Duration::from_secs(secs))
+                .map(|m| m.bitrate)
+        };
+
+        assert_eq!(bitrate(12), Some(500));
+        assert_eq!(bitrate(32), Some(500));
+    }
+}
@@ -3,36 +3,70 @@ use log::error;
 use serde::{Deserialize, Serialize};
 use tracing::trace;
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, Metrics, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct RistStats {
     #[serde(rename = "receiver-stats")]
     receiver_stats: Option<ReceiverStats>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ReceiverStats {
     flowinstant: Flowinstant,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Flowinstant {
+    #[serde(default)]
+    stats: FlowStats,
     peers: Vec<Peer>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct FlowStats {
+    pub quality: f64,
+    pub received: u64,
+    pub missing: u64,
+    pub lost: u64,
+    pub recovered_total: u64,
+    pub retries: u64,
+    pub dropped_late: u64,
+    pub dropped_full: u64,
+    pub bitrate: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Peer {
+    #[serde(default)]
+    id: u64,
+    #[serde(default)]
+    dead: u8,
     stats: PeerStats,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct PeerStats {
     pub rtt: f64,
     pub avg_rtt: f64,
-    pub bitrate: usize,
-    pub avg_bitrate: usize,
+    pub bitrate: u64,
+    pub avg_bitrate: u64,
+}
+
+/// How the RTT of multiple peers gets combined
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RttMode {
+    #[default]
+    Average,
+    Worst,
+}
+
+impl RttMode {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,13 +75,57 @@ pub struct Rist {
     /// URL to RIST stats page
     pub stats_url: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// Only use these peer ids, all live peers are used when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<u64>,
+
+    /// How to combine the RTT of bonded peers
+    #[serde(default, skip_serializing_if = "RttMode::is_default")]
+    pub rtt_mode: RttMode,
+
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<RistStats>,
+}
+
+/// Combined stats of the selected peers
+#[derive(Debug, PartialEq)]
+pub struct Aggregate {
+    /// Bitrate in Kbps
+    pub bitrate: u32,
+    pub rtt: f64,
+    pub lost: u64,
+    pub recovered: u64,
+    pub retries: u64,
+    pub quality: f64,
+    pub peers: Vec<PeerSummary>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PeerSummary {
+    pub id: u64,
+    /// Bitrate in Kbps
+    pub bitrate: u32,
+    pub rtt: f64,
+}
+
+fn kbps(bits: u64) -> u32 {
+    (bits / 1024).try_into().unwrap_or(u32::MAX)
 }
 
 impl Rist {
     pub async fn get_stats(&self) -> Option<RistStats> {
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
+
+    async fn fetch_stats(&self) -> Option<RistStats> {
         let res = match self.client.get(&self.stats_url).send().await {
             Ok(res) => res,
             Err(e) => {
@@ -72,55 +150,73 @@ impl Rist {
         trace!("{:#?}", stream);
         Some(stream)
     }
+
+    /// Stats of the selected peers combined, None when no peer is connected
+    pub async fn get_aggregate(&self) -> Option<Aggregate> {
+        let flow = self.get_stats().await?.receiver_stats?.flowinstant;
+        self.aggregate(&flow)
+    }
+
+    fn aggregate(&self, flow: &Flowinstant) -> Option<Aggregate> {
+        let peers = flow
+            .peers
+            .iter()
+            .filter(|p| p.dead == 0)
+            .filter(|p| self.peers.is_empty() || self.peers.contains(&p.id))
+            .collect::<Vec<_>>();
+
+        if peers.is_empty() {
+            return None;
+        }
+
+        let rtt = match self.rtt_mode {
+            RttMode::Average => peers.iter().map(|p| p.stats.rtt).sum::<f64>() / peers.len() as f64,
+            RttMode::Worst => peers.iter().map(|p| p.stats.rtt).fold(0.0, f64::max),
+        };
+
+        Some(Aggregate {
+            bitrate: kbps(peers.iter().map(|p| p.stats.bitrate).sum()),
+            rtt,
+            lost: flow.stats.lost,
+            recovered: flow.stats.recovered_total,
+            retries: flow.stats.retries,
+            quality: flow.stats.quality,
+            peers: peers
+                .iter()
+                .map(|p| PeerSummary {
+                    id: p.id,
+                    bitrate: kbps(p.stats.bitrate),
+                    rtt: p.stats.rtt,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl From<&Aggregate> for Metrics {
+    fn from(stats: &Aggregate) -> Self {
+        Metrics {
+            bitrate: stats.bitrate,
+            rtt: Some(stats.rtt),
+            loss: Some(stats.lost),
+        }
+    }
 }
 
 #[async_trait]
 #[typetag::serde]
 impl SwitchLogic for Rist {
     async fn switch(&self, triggers: &Triggers) -> SwitchType {
-        let stats = match self
-            .get_stats()
-            .await
-            .and_then(|stats| stats.receiver_stats)
-        {
-            Some(s) => s.flowinstant.peers,
+        let stats = match self.get_aggregate().await {
+            Some(stats) => stats,
             None => return SwitchType::Offline,
         };
 
-        let bitrate: u32 = (stats.iter().map(|p| p.stats.bitrate).sum::<usize>() / 1024)
-            .try_into()
-            .unwrap();
-        let rtt = stats.iter().map(|p| p.stats.rtt).sum::<f64>() / stats.len() as f64;
-
-        if let Some(offline) = triggers.offline {
-            if bitrate > 0 && bitrate <= offline {
-                return SwitchType::Offline;
-            }
-        }
-
-        if let Some(rtt_offline) = triggers.rtt_offline {
-            if rtt >= rtt_offline.into() {
-                return SwitchType::Offline;
-            }
-        }
-
-        if bitrate == 0 {
+        if stats.bitrate == 0 {
             return SwitchType::Offline;
         }
 
-        if let Some(low) = triggers.low {
-            if bitrate <= low {
-                return SwitchType::Low;
-            }
-        }
-
-        if let Some(rtt_trigger) = triggers.rtt {
-            if rtt >= rtt_trigger.into() {
-                return SwitchType::Low;
-            }
-        }
-
-        SwitchType::Normal
+        Metrics::from(&stats).switch_type(triggers)
     }
 }
 
@@ -128,38 +224,51 @@ impl SwitchLogic for Rist {
 #[typetag::serde]
 impl StreamServersCommands for Rist {
     async fn bitrate(&self) -> super::Bitrate {
-        let stats = match self
-            .get_stats()
-            .await
-            .and_then(|stats| stats.receiver_stats)
-        {
-            Some(s) => s.flowinstant.peers,
+        let stats = match self.get_aggregate().await {
+            Some(stats) => stats,
             None => return super::Bitrate { message: None },
         };
 
-        let bitrate: u32 = (stats.iter().map(|p| p.stats.bitrate).sum::<usize>() / 1024)
-            .try_into()
-            .unwrap();
-        let rtt = stats.iter().map(|p| p.stats.rtt).sum::<f64>() / stats.len() as f64;
-
-        let message = format!("{}, {} ms", bitrate, rtt.round());
+        let message = format!("{}, {} ms", stats.bitrate, stats.rtt.round());
         super::Bitrate {
             message: Some(message),
         }
     }
 
-    // TODO: Add more fields.
-    async fn source_info(&self) -> Option<String> {
-        let stats = self.get_stats().await?.receiver_stats?.flowinstant.peers;
+    async fn source_info(&self) -> Option<super::SourceInfo> {
+        let stats = self.get_aggregate().await?;
+
+        let mut info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with("rtt", stats.rtt)
+            .with("lost", stats.lost)
+            .with("recovered", stats.recovered)
+            .with("retries", stats.retries);
 
-        let bitrate: u32 = (stats.iter().map(|p| p.stats.bitrate).sum::<usize>() / 1024)
-            .try_into()
-            .unwrap();
-        let rtt = stats.iter().map(|p| p.stats.rtt).sum::<f64>() / stats.len() as f64;
+        if stats.quality > 0.0 {
+            info = info.with("quality", stats.quality);
+        }
 
-        let bitrate = format!("{} Kbps, {} ms", bitrate, rtt.round());
+        if stats.peers.len() > 1 {
+            for peer in &stats.peers {
+                info = info.with_values(
+                    "peer",
+                    [
+                        ("id", peer.id.into()),
+                        ("bitrate", peer.bitrate.into()),
+                        ("rtt", peer.rtt.into()),
+                    ],
+                );
+            }
+        }
 
-        Some(bitrate)
+        Some(info)
+    }
+
+    async fn metrics(&self) -> Option<Metrics> {
+        self.get_aggregate()
+            .await
+            .map(|stats| Metrics::from(&stats))
     }
 }
 
@@ -203,4 +312,38 @@ mod tests {
         let peer_stats = &receiver_stats.flowinstant.peers[0].stats;
         assert_eq!(peer_stats.bitrate, 6651751, "Bitrate should be 6651751");
     }
+
+    fn rist(peers: Vec<u64>, rtt_mode: RttMode) -> Rist {
+        Rist {
+            stats_url: String::new(),
+            peers,
+            rtt_mode,
+            client: Default::default(),
+            recent: Default::default(),
+        }
+    }
+
+    #[test]
+    fn bonded_peers() {
+        let s = r#"{"receiver-stats":{"flowinstant":{"flow_id":1,"dead":0,"stats":{"quality":97.5,"received":1300,"missing":4,"lost":2,"recovered_total":30,"retries":41,"bitrate":8192000},"peers":[{"id":1,"dead":0,"stats":{"rtt":100.0,"avg_rtt":100.0,"bitrate":4096000,"avg_bitrate":4096000}},{"id":2,"dead":0,"stats":{"rtt":300.0,"avg_rtt":300.0,"bitrate":2048000,"avg_bitrate":2048000}},{"id":3,"dead":1,"stats":{"rtt":900.0,"avg_rtt":900.0,"bitrate":2048000,"avg_bitrate":2048000}}]}}}"#;
+        let parsed: RistStats = serde_json::from_str(s).unwrap();
+        let flow = parsed.receiver_stats.unwrap().flowinstant;
+
+        let average = rist(vec![], RttMode::Average).aggregate(&flow).unwrap();
+        assert_eq!(average.bitrate, 6000);
+        assert_eq!(average.rtt, 200.0);
+        assert_eq!(average.lost, 2);
+        assert_eq!(average.recovered, 30);
+        assert_eq!(average.retries, 41);
+        assert_eq!(average.peers.len(), 2, "Dead peers should be skipped");
+
+        let worst = rist(vec![], RttMode::Worst).aggregate(&flow).unwrap();
+        assert_eq!(worst.rtt, 300.0);
+
+        let selected = rist(vec![2], RttMode::Average).aggregate(&flow).unwrap();
+        assert_eq!(selected.bitrate, 2000);
+        assert_eq!(selected.peers[0].id, 2);
+
+        assert!(rist(vec![3], RttMode::Average).aggregate(&flow).is_none());
+    }
 }
@@ -1,9 +1,8 @@
 use async_trait::async_trait;
-use log::{error, trace};
+use log::trace;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -30,28 +29,18 @@ pub struct SrtLiveServer {
     /// StreamID of the where you are publishing the feed. (ex; publish/live/feed1 )
     pub publisher: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
 }
 
 impl SrtLiveServer {
     pub async fn get_stats(&self) -> Option<Stat> {
-        let res = match self.client.get(&self.stats_url).send().await {
-            Ok(res) => res,
-            Err(_) => {
-                error!("Stats page ({}) is unreachable", self.stats_url);
-                return None;
-            }
-        };
-
-        if res.status() != reqwest::StatusCode::OK {
-            error!("Error accessing stats page ({})", self.stats_url);
-            return None;
-        }
-
-        let text = res.text().await.ok()?;
-        let data: Value = serde_json::from_str(&text).ok()?;
+        let data = stats_cache::get_json(&self.client, &self.stats_url).await?;
         let publisher = &data["publishers"][&self.publisher];
 
         let stream: Stat = serde_json::from_value(publisher.to_owned()).ok()?;
@@ -125,26 +114,30 @@ impl StreamServersCommands for SrtLiveServer {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
 
-        let bitrate = format!("{} Kbps, {} ms", stats.bitrate, stats.rtt.round());
-
-        let mbps = format!(
-            "Estimated bandwidth {} Mbps, Receiving rate {:.2} Mbps",
-            stats.mbps_bandwidth.round(),
-            stats.mbps_recv_rate
-        );
-
-        let pkt = format!(
-            "{} dropped, {} loss",
-            stats.pkt_rcv_drop, stats.pkt_rcv_loss
-        );
+        let info = super::SourceInfo::default()
+            .with("bitrate", stats.bitrate)
+            .with("rtt", stats.rtt)
+            .with("bandwidth", stats.mbps_bandwidth)
+            .with("receiveRate", stats.mbps_recv_rate)
+            .with("dropped", stats.pkt_rcv_drop)
+            .with("lost", stats.pkt_rcv_loss)
+            // The ms of acknowledged packets in the receiver's buffer
+            .with("buffer", stats.ms_rcv_buf);
+
+        Some(info)
+    }
 
-        // The ms of acknowledged packets in the receiver's buffer
-        let ms_buf = format!("{} ms buffer", stats.ms_rcv_buf);
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
 
-        Some(format!("{} | {} | {} |  {}", bitrate, mbps, pkt, ms_buf))
+        Some(super::Metrics {
+            bitrate: stats.bitrate.max(0) as u32,
+            rtt: Some(stats.rtt),
+            loss: Some(stats.pkt_rcv_loss.max(0) as u64),
+        })
     }
 }
 
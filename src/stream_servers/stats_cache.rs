@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use tracing::error;
+
+use super::{http::HttpOptions, HttpClient};
+
+/// How long a fetched stats page will be reused
+const TTL: Duration = Duration::from_millis(500);
+
+type Entry = Arc<tokio::sync::Mutex<Option<Fetched<Option<Arc<Value>>>>>>;
+
+/// Stats pages shared between every stream server in the process
+static CACHE: Lazy<Mutex<HashMap<(String, HttpOptions), Entry>>> = Lazy::new(Default::default);
+
+struct Fetched<T> {
+    at: Instant,
+    data: T,
+}
+
+/// Gets the JSON stats page at the url, concurrent requests for the same
+/// url and HTTP options will share a single HTTP request and parse.
+pub async fn get_json(client: &HttpClient, url: &str) -> Option<Arc<Value>> {
+    let entry = CACHE
+        .lock()
+        .unwrap()
+        .entry((url.to_owned(), client.options().clone()))
+        .or_default()
+        .clone();
+
+    let mut fetched = entry.lock().await;
+
+    if let Some(fetched) = &*fetched {
+        if fetched.at.elapsed() < TTL {
+            return fetched.data.clone();
+        }
+    }
+
+    let data = fetch(client, url).await.map(Arc::new);
+
+    *fetched = Some(Fetched {
+        at: Instant::now(),
+        data: data.clone(),
+    });
+
+    data
+}
+
+/// The stats a stream server fetched last, reused for a short moment so
+/// the switch decision and the checks of the switcher share one fetch
+pub struct Recent<T> {
+    last: tokio::sync::Mutex<Option<Fetched<Option<T>>>>,
+}
+
+impl<T> Default for Recent<T> {
+    fn default() -> Self {
+        Self {
+            last: Default::default(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Recent<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recent").finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone> Recent<T> {
+    /// Gets the stats fetched less than [`TTL`] ago, or waits for the fetch
+    pub async fn get_or_fetch(
+        &self,
+        fetch: impl std::future::Future<Output = Option<T>>,
+    ) -> Option<T> {
+        let mut last = self.last.lock().await;
+
+        if let Some(fetched) = &*last {
+            if fetched.at.elapsed() < TTL {
+                return fetched.data.clone();
+            }
+        }
+
+        let data = fetch.await;
+
+        *last = Some(Fetched {
+            at: Instant::now(),
+            data: data.clone(),
+        });
+
+        data
+    }
+}
+
+async fn fetch(client: &HttpClient, url: &str) -> Option<Value> {
+    let res = match client.get(url).send().await {
+        Ok(res) => res,
+        Err(e) => {
+            error!("Stats page ({}) is unreachable, {}", url, e);
+            return None;
+        }
+    };
+
+    if res.status() != reqwest::StatusCode::OK {
+        error!("Error accessing stats page ({})", url);
+        return None;
+    }
+
+    let text = res.text().await.ok()?;
+
+    match serde_json::from_str(&text) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            error!("Error parsing stats page ({}) {}", url, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use warp::Filter;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_requests_share_fetch() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        let route = warp::path("stats").map(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            warp::reply::json(&serde_json::json!({ "publishers": {} }))
+        });
+
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpClient::default();
+        let url = format!("http://{}/stats", addr);
+
+        let polls = (0..20).map(|_| get_json(&client, &url));
+        let results = futures_util::future::join_all(polls).await;
+
+        assert!(results.iter().all(|r| r.is_some()));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(TTL).await;
+        get_json(&client, &url).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn recent_stats_reused() {
+        let recent = Recent::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Some(6000)
+        };
+
+        assert_eq!(recent.get_or_fetch(fetch()).await, Some(6000));
+        assert_eq!(recent.get_or_fetch(fetch()).await, Some(6000));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(TTL).await;
+        recent.get_or_fetch(fetch()).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+}
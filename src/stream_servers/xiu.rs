@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use log::{error, trace};
 use serde::{Deserialize, Serialize};
 
-use super::{default_reqwest_client, Bsl, StreamServersCommands, SwitchLogic};
+use super::{stats_cache, Bsl, HttpClient, StreamServersCommands, SwitchLogic};
 use crate::switcher::{SwitchType, Triggers};
 
 #[derive(Deserialize, Debug)]
@@ -11,7 +11,7 @@ pub struct XiuStreamInfo {
     pub subscriber_count: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct XiuPublisher {
     pub id: String,
     pub identifier: XiuIdentifier,
@@ -75,13 +75,24 @@ pub struct Xiu {
     /// Stream key
     pub key: String,
 
-    /// Client to make HTTP requests with
-    #[serde(skip, default = "default_reqwest_client")]
-    pub client: reqwest::Client,
+    /// HTTP options used by the client to make HTTP requests with
+    #[serde(
+        rename = "http",
+        default,
+        skip_serializing_if = "HttpClient::is_default"
+    )]
+    pub client: HttpClient,
+
+    #[serde(skip)]
+    pub recent: stats_cache::Recent<XiuPublisher>,
 }
 
 impl Xiu {
     pub async fn get_stats(&self) -> Option<XiuPublisher> {
+        self.recent.get_or_fetch(self.fetch_stats()).await
+    }
+
+    async fn fetch_stats(&self) -> Option<XiuPublisher> {
         let body = serde_json::json!({
             "identifier": {
                 "rtmp": {
@@ -196,16 +207,31 @@ impl StreamServersCommands for Xiu {
         }
     }
 
-    async fn source_info(&self) -> Option<String> {
+    async fn source_info(&self) -> Option<super::SourceInfo> {
         let stats = self.get_stats().await?;
+        let video = stats.video.as_ref()?;
+
+        let info = super::SourceInfo::default()
+            .with("bitrate", video.bitrate)
+            .with_values(
+                "resolution",
+                [
+                    ("width", video.width.into()),
+                    ("height", video.height.into()),
+                ],
+            )
+            .with("videoCodec", video.codec.as_str());
+
+        Some(info)
+    }
 
-        stats.video.as_ref()?;
+    async fn metrics(&self) -> Option<super::Metrics> {
+        let stats = self.get_stats().await?;
 
-        let video_info = stats.video.as_ref().unwrap();
-        Some(format!(
-            "{}x{} {} Kbps, {}",
-            video_info.width, video_info.height, video_info.bitrate, video_info.codec
-        ))
+        Some(super::Metrics {
+            bitrate: stats.recv_bitrate.try_into().unwrap_or(u32::MAX),
+            ..Default::default()
+        })
     }
 }
 
@@ -1,11 +1,12 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use serde::{Deserialize, Serialize};
 use tokio::sync::Notify;
-use tracing::{debug, error, info, Instrument};
+use tracing::{debug, error, info, warn, Instrument};
 
 use crate::{
-    chat, error,
+    broadcasting_software::{BroadcastingSoftwareLogic, MediaState},
+    chat, config, error,
     noalbs::{self, ChatSender},
     state::ClientStatus,
     stream_servers,
@@ -24,27 +25,44 @@ impl Switcher {
             let mut prev_switch_type: SwitchType = SwitchType::Offline;
             let mut same_type: u8 = 0;
             let mut same_type_seconds = 0;
+            let mut format_issue = None;
+            let mut overlay = None;
+            let mut watchdog = WatchdogState::default();
 
             loop {
                 tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
                 tracing::debug!("Switcher loop");
 
                 if let Some(notifier) = switcher.get_sleep_notifier_if_necessary().await {
+                    // Everything gets applied again once the switcher runs,
+                    // OBS might have been restarted in the meantime
+                    switcher.state.write().await.switcher_state.applied_switch = None;
+                    overlay = None;
+
                     notifier.notified().await;
                     info!("Switcher running");
                     continue;
                 }
 
+                let mut decision = None;
+
                 if let Err(e) = switcher
                     .switch(
                         &mut prev_switch_type,
                         &mut same_type,
                         &mut same_type_seconds,
+                        &mut format_issue,
+                        &mut decision,
                     )
                     .await
                 {
                     error!("Error when trying to switch: {}", e);
                 }
+
+                if let Some(decision) = decision {
+                    switcher.update_overlay(&decision, &mut overlay).await;
+                    switcher.check_stuck_source(&decision, &mut watchdog).await;
+                }
             }
         }
         .instrument(tracing::info_span!("Switcher"));
@@ -91,6 +109,8 @@ impl Switcher {
         prev_switch_type: &mut SwitchType,
         same_type: &mut u8,
         same_type_seconds: &mut u32,
+        prev_format_issue: &mut Option<FormatIssue>,
+        decision: &mut Option<Decision>,
     ) -> Result<(), error::Error> {
         let state = self.state.read().await;
 
@@ -103,6 +123,34 @@ impl Switcher {
         let (mut server, mut current_switch_type) =
             Self::get_online_stream_server(stream_servers, triggers).await;
 
+        if let (Some(s), SwitchType::Normal) = (server, current_switch_type) {
+            if let Some(issue) = Self::check_format(s, &triggers.format).await {
+                if triggers.format.switch_to_low {
+                    current_switch_type = SwitchType::Low;
+                }
+
+                if prev_format_issue.as_ref() != Some(&issue) {
+                    self.notify_format_issue(&state, issue.to_owned()).await;
+                }
+
+                *prev_format_issue = Some(issue);
+            } else {
+                *prev_format_issue = None;
+            }
+        }
+
+        // Comes from the stats the switch decision fetched
+        let metrics = match (server, &switcher_config.overlay) {
+            (Some(s), Some(_)) => s.stream_server.metrics().await,
+            _ => None,
+        };
+
+        *decision = Some(Decision {
+            server: server.map(|s| s.name.to_owned()),
+            switch_type: current_switch_type,
+            metrics,
+        });
+
         // When stream comes back from offline, instantly switch.
         let mut force_switch = *instant_recover
             && *prev_switch_type == SwitchType::Offline
@@ -204,6 +252,16 @@ impl Switcher {
         }
         .to_owned();
 
+        let transition = scenes
+            .transitions
+            .type_to_transition(&current_switch_type)
+            .cloned();
+
+        let actions = scenes
+            .actions
+            .type_to_actions(&current_switch_type)
+            .to_vec();
+
         let server_name = server.map(|s| s.name.to_owned());
 
         drop(state);
@@ -222,7 +280,7 @@ impl Switcher {
             }
         }
 
-        self.switch_if_necessary(&scene, current_switch_type)
+        self.switch_if_necessary(&scene, current_switch_type, transition.as_ref(), &actions)
             .await?;
 
         Ok(())
@@ -250,20 +308,174 @@ impl Switcher {
         (None, SwitchType::Offline)
     }
 
+    /// Checks the stream metadata of the server against the format triggers
+    async fn check_format(
+        server: &stream_servers::StreamServer,
+        triggers: &FormatTriggers,
+    ) -> Option<FormatIssue> {
+        if !triggers.is_enabled() {
+            return None;
+        }
+
+        let metadata = server.stream_server.metadata().await?;
+        triggers.check(&metadata)
+    }
+
+    /// Writes the metrics of the server into the text source,
+    /// only when the text or color changed
+    async fn update_overlay(&self, decision: &Decision, prev_overlay: &mut Option<OverlayText>) {
+        let (overlay, connection) = {
+            let state = self.state.read().await;
+
+            let Some(overlay) = state.config.switcher.overlay.clone() else {
+                return;
+            };
+
+            let Some(connection) = state.broadcasting_software.connection.clone() else {
+                return;
+            };
+
+            (overlay, connection)
+        };
+
+        let text = overlay.render(
+            decision.server.as_deref(),
+            decision.metrics.as_ref(),
+            decision.switch_type,
+        );
+
+        if prev_overlay.as_ref() == Some(&text) {
+            return;
+        }
+
+        match connection
+            .set_text(&overlay.source, &text.text, text.color)
+            .await
+        {
+            Ok(()) => *prev_overlay = Some(text),
+            Err(e) => {
+                error!("Unable to update overlay {}: {}", overlay.source, e);
+                *prev_overlay = None;
+            }
+        }
+    }
+
+    /// Refreshes the media source when it stopped playing or its cursor
+    /// stopped moving while the stream server reports a healthy bitrate
+    async fn check_stuck_source(&self, decision: &Decision, watchdog_state: &mut WatchdogState) {
+        let (watchdog, connection) = {
+            let state = self.state.read().await;
+
+            let Some(watchdog) = state.config.switcher.watchdog.clone() else {
+                return;
+            };
+
+            let Some(connection) = state.broadcasting_software.connection.clone() else {
+                return;
+            };
+
+            (watchdog, connection)
+        };
+
+        if decision.server.is_none() || decision.switch_type != SwitchType::Normal {
+            watchdog_state.reset();
+            return;
+        }
+
+        let status = match connection.get_media_source_status(&watchdog.source).await {
+            Ok(status) => (status.state == MediaState::Playing, status.cursor),
+            Err(e) => {
+                // The watchdog would never fire, for example with a browser source
+                if !watchdog_state.unreadable {
+                    warn!(
+                        "Unable to get the status of {}, the watchdog can't check it: {}",
+                        watchdog.source, e
+                    );
+                }
+
+                watchdog_state.unreadable = true;
+                watchdog_state.reset();
+                return;
+            }
+        };
+
+        watchdog_state.unreadable = false;
+
+        if !watchdog_state.check(&watchdog, status, Instant::now()) {
+            return;
+        }
+
+        info!(
+            "Media source {} looks stuck, trying to fix it",
+            watchdog.source
+        );
+
+        // OBS v4 takes a second to fix the source, don't hold the state meanwhile
+        if let Err(e) = connection.fix(Some(&watchdog.source)).await {
+            error!("Unable to fix {}: {}", watchdog.source, e);
+            return;
+        }
+
+        let state = self.state.read().await;
+
+        if !state.broadcasting_software.is_streaming {
+            return;
+        }
+
+        if let Some(chat) = &state.config.chat {
+            let message = chat::HandleMessage::InternalChatUpdate(chat::InternalChatUpdate {
+                platform: chat.platform.kind(),
+                channel: chat.username.to_owned(),
+                kind: chat::InternalUpdate::StuckSource(watchdog.source.to_owned()),
+            });
+
+            drop(state);
+            let _ = self.chat_sender.send(message).await;
+        }
+    }
+
+    async fn notify_format_issue(
+        &self,
+        state: &tokio::sync::RwLockReadGuard<'_, crate::state::State>,
+        issue: FormatIssue,
+    ) {
+        info!("Unexpected stream format: {:?}", issue);
+
+        if !state.broadcasting_software.is_streaming {
+            return;
+        }
+
+        if let Some(chat) = &state.config.chat {
+            let message = chat::HandleMessage::InternalChatUpdate(chat::InternalChatUpdate {
+                platform: chat.platform.kind(),
+                channel: chat.username.to_owned(),
+                kind: chat::InternalUpdate::UnexpectedFormat(issue),
+            });
+
+            let _ = self.chat_sender.send(message).await;
+        }
+    }
+
     pub async fn switch_if_necessary(
         &self,
         switch_scene: &str,
         switch_type: SwitchType,
+        transition: Option<&Transition>,
+        actions: &[SwitchAction],
     ) -> Result<(), error::Error> {
         debug!(
             "Switch scene: {} Switch type: {:?}",
             switch_scene, switch_type
         );
 
-        let state = &self.state.read().await;
+        let state = self.state.read().await;
         let current_scene = &state.broadcasting_software.current_scene;
+        let applied = state.switcher_state.applied_switch.as_ref();
+        let same = applied.is_some_and(|a| a.is(switch_type, switch_scene));
 
-        if current_scene == switch_scene {
+        let actions = pending_actions(applied, actions, current_scene, switch_scene, switch_type);
+
+        if actions.is_empty() {
             return Ok(());
         }
 
@@ -272,7 +484,7 @@ impl Switcher {
             .optional_scenes
             .starting
             .as_ref()
-            .map_or(false, |starting_scene| {
+            .is_some_and(|starting_scene| {
                 let switch_to_live = state
                     .config
                     .optional_options
@@ -291,41 +503,331 @@ impl Switcher {
             return Ok(());
         }
 
-        // Ignore the error.. it should work at some point
-        if let Err(error) = state
+        let connection = state
             .broadcasting_software
             .connection
+            .clone()
+            .ok_or(error::Error::NoSoftwareSet)?;
+
+        let preview_only = actions.contains(&&SwitchAction::SwitchScene)
+            && Self::is_preview_only(&state, switch_type);
+
+        let options = &state.config.optional_options;
+        let save_replay = !same
+            && match switch_type {
+                SwitchType::Low => options.save_replay_buffer_on_low,
+                SwitchType::Offline => options.save_replay_buffer_on_offline,
+                _ => false,
+            };
+
+        // Nothing went to the program scene with only a suggestion
+        let notify = state.broadcasting_software.is_streaming
+            && state.config.switcher.auto_switch_notification
+            && !preview_only;
+
+        let chat = state
+            .config
+            .chat
             .as_ref()
-            .ok_or(error::Error::NoSoftwareSet)?
-            .switch_scene(switch_scene)
+            .filter(|_| notify)
+            .map(|chat| (chat.platform.kind(), chat.username.to_owned()));
+
+        drop(state);
+
+        Self::run_actions(
+            connection.as_ref(),
+            &actions,
+            switch_scene,
+            switch_type,
+            transition,
+            preview_only,
+        )
+        .await;
+
+        // A failed scene switch gets retried because the scene didn't change,
+        // failed source actions only get logged once
+        self.state.write().await.switcher_state.applied_switch = Some(AppliedSwitch {
+            switch_type,
+            scene: switch_scene.to_owned(),
+            preview_only,
+        });
+
+        if preview_only {
+            info!("Suggested [{:?}] {} in preview", switch_type, switch_scene);
+        } else {
+            info!("Switched to [{:?}] {}", switch_type, switch_scene);
+        }
+
+        if save_replay {
+            // OBS needs some time to write the replay
+            tokio::spawn(async move {
+                match connection.save_replay_buffer().await {
+                    Ok(path) => info!("Replay saved to {}", path),
+                    Err(error) => error!("Save replay buffer error {:?}", error),
+                }
+            });
+        }
+
+        if let Some((platform, channel)) = chat {
+            let message =
+                chat::HandleMessage::AutomaticSwitchingScene(chat::AutomaticSwitchingScene {
+                    platform,
+                    channel,
+                    scene: switch_scene.to_owned(),
+                    switch_type,
+                });
+
+            let _ = self.chat_sender.send(message).await;
+        }
+
+        Ok(())
+    }
+
+    /// Low and offline are left as a suggestion for the operator when
+    /// studio mode is on
+    fn is_preview_only(
+        state: &tokio::sync::RwLockReadGuard<'_, crate::state::State>,
+        switch_type: SwitchType,
+    ) -> bool {
+        let studio_mode = state
+            .config
+            .software
+            .obs_config()
+            .map(|c| c.studio_mode)
+            .unwrap_or_default();
+
+        state.broadcasting_software.studio_mode
+            && studio_mode == config::StudioMode::Preview
+            && matches!(switch_type, SwitchType::Low | SwitchType::Offline)
+    }
+
+    /// Runs every action, a failing action doesn't stop the others.
+    /// Returns whether all of them succeeded.
+    async fn run_actions(
+        connection: &dyn BroadcastingSoftwareLogic,
+        actions: &[&SwitchAction],
+        switch_scene: &str,
+        switch_type: SwitchType,
+        transition: Option<&Transition>,
+        preview_only: bool,
+    ) -> bool {
+        let mut success = true;
+
+        for action in actions {
+            if let Err(error) = Self::run_action(
+                connection,
+                action,
+                switch_scene,
+                switch_type,
+                transition,
+                preview_only,
+            )
             .await
-        {
-            error!("Switch scene error {:?}", error);
-            return Ok(());
+            {
+                error!("Switch action {:?} error {:?}", action, error);
+                success = false;
+            }
         }
 
-        info!("Scene switched to [{:?}] {}", switch_type, switch_scene);
+        success
+    }
 
-        if state.broadcasting_software.is_streaming
-            && state.config.switcher.auto_switch_notification
-        {
-            if let Some(chat) = &state.config.chat {
-                let message =
-                    chat::HandleMessage::AutomaticSwitchingScene(chat::AutomaticSwitchingScene {
-                        platform: chat.platform.kind(),
-                        channel: chat.username.to_owned(),
-                        scene: switch_scene.to_owned(),
-                        switch_type,
-                    });
-
-                let _ = self.chat_sender.send(message).await;
+    async fn run_action(
+        connection: &dyn BroadcastingSoftwareLogic,
+        action: &SwitchAction,
+        switch_scene: &str,
+        switch_type: SwitchType,
+        transition: Option<&Transition>,
+        preview_only: bool,
+    ) -> Result<(), error::Error> {
+        match action {
+            SwitchAction::SwitchScene => {
+                Self::switch_scene(
+                    connection,
+                    switch_scene,
+                    switch_type,
+                    transition,
+                    preview_only,
+                )
+                .await
             }
+            action => run_source_action(connection, action).await,
         }
+    }
+
+    async fn switch_scene(
+        connection: &dyn BroadcastingSoftwareLogic,
+        switch_scene: &str,
+        switch_type: SwitchType,
+        transition: Option<&Transition>,
+        preview_only: bool,
+    ) -> Result<(), error::Error> {
+        if preview_only {
+            connection.set_preview_scene(switch_scene).await?;
+            info!("Preview scene set to [{:?}] {}", switch_type, switch_scene);
+
+            return Ok(());
+        }
+
+        match transition {
+            Some(transition) => {
+                connection
+                    .switch_scene_with_transition(switch_scene, transition)
+                    .await?
+            }
+            None => connection.switch_scene(switch_scene).await?,
+        };
+
+        info!("Scene switched to [{:?}] {}", switch_type, switch_scene);
 
         Ok(())
     }
 }
 
+/// Actions that still need to run for the switch. A repeated poll of the
+/// same switch only switches back when the scene got changed, and not when
+/// the scene was left in the preview for the operator.
+fn pending_actions<'a>(
+    applied: Option<&AppliedSwitch>,
+    actions: &'a [SwitchAction],
+    current_scene: &str,
+    switch_scene: &str,
+    switch_type: SwitchType,
+) -> Vec<&'a SwitchAction> {
+    let applied = applied.filter(|a| a.is(switch_type, switch_scene));
+    let previewed = applied.is_some_and(|a| a.preview_only);
+
+    actions
+        .iter()
+        .filter(|a| match a {
+            SwitchAction::SwitchScene => current_scene != switch_scene && !previewed,
+            _ => applied.is_none(),
+        })
+        .collect()
+}
+
+/// What the switcher decided on with the stats of a check
+#[derive(Debug)]
+pub struct Decision {
+    pub server: Option<String>,
+    pub switch_type: SwitchType,
+    pub metrics: Option<stream_servers::Metrics>,
+}
+
+/// The switch that was last applied by the switcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedSwitch {
+    pub switch_type: SwitchType,
+    pub scene: String,
+
+    /// The scene was only set as the preview scene
+    pub preview_only: bool,
+}
+
+impl AppliedSwitch {
+    fn is(&self, switch_type: SwitchType, scene: &str) -> bool {
+        self.switch_type == switch_type && self.scene == scene
+    }
+}
+
+/// Runs an action that doesn't need a switch, switching scenes is skipped
+pub async fn run_source_action(
+    connection: &dyn BroadcastingSoftwareLogic,
+    action: &SwitchAction,
+) -> Result<(), error::Error> {
+    match action {
+        SwitchAction::SwitchScene => Ok(()),
+        SwitchAction::SceneItem { source, enabled } => {
+            connection.set_scene_item_enabled(source, *enabled).await
+        }
+        SwitchAction::Filter {
+            source,
+            filter,
+            enabled,
+        } => {
+            connection
+                .set_filter_enabled(source, filter, *enabled)
+                .await
+        }
+        SwitchAction::Mute { input, muted } => connection.set_input_muted(input, *muted).await,
+    }
+}
+
+/// Lets chat and the WS clients know that the monitored input went silent
+/// or got its audio back, and runs the configured actions
+pub async fn handle_audio_silence(state: noalbs::UserState, chat_sender: ChatSender, silent: bool) {
+    let state = state.read().await;
+
+    let Some(monitor) = &state.config.switcher.audio_monitor else {
+        return;
+    };
+
+    info!(
+        "Input {} {}",
+        monitor.input,
+        if silent {
+            "is silent"
+        } else {
+            "has audio again"
+        }
+    );
+
+    state.send_event(crate::events::Event::AudioSilence {
+        input: &monitor.input,
+        silent,
+    });
+
+    if let Some(chat) = &state.config.chat {
+        let message = chat::HandleMessage::InternalChatUpdate(chat::InternalChatUpdate {
+            platform: chat.platform.kind(),
+            channel: chat.username.to_owned(),
+            kind: chat::InternalUpdate::AudioSilence {
+                input: monitor.input.to_owned(),
+                silent,
+            },
+        });
+
+        let _ = chat_sender.send(message).await;
+    }
+
+    run_audio_monitor_actions(&state, monitor, silent).await;
+}
+
+/// Undoes the actions taken for the silence when the stream stopped,
+/// without announcing that the input has audio again
+pub async fn undo_audio_silence(state: noalbs::UserState) {
+    let state = state.read().await;
+
+    let Some(monitor) = &state.config.switcher.audio_monitor else {
+        return;
+    };
+
+    info!("Stream stopped while {} was silent", monitor.input);
+    run_audio_monitor_actions(&state, monitor, false).await;
+}
+
+async fn run_audio_monitor_actions(
+    state: &tokio::sync::RwLockReadGuard<'_, crate::state::State>,
+    monitor: &AudioMonitor,
+    silent: bool,
+) {
+    let Some(connection) = &state.broadcasting_software.connection else {
+        return;
+    };
+
+    let actions = if silent {
+        &monitor.actions
+    } else {
+        &monitor.recover_actions
+    };
+
+    for action in actions {
+        if let Err(e) = run_source_action(connection.as_ref(), action).await {
+            error!("Audio monitor action {:?} error: {}", action, e);
+        }
+    }
+}
+
 async fn get_optional_scenes<'a>(
     server: Option<&'a stream_servers::StreamServer>,
     state: &tokio::sync::RwLockReadGuard<'_, crate::state::State>,
@@ -362,6 +864,14 @@ pub struct SwitchingScenes {
     pub normal: String,
     pub low: String,
     pub offline: String,
+
+    /// Transitions used to switch to the scenes instead of the active one
+    #[serde(default, skip_serializing_if = "SceneTransitions::is_empty")]
+    pub transitions: SceneTransitions,
+
+    /// Actions to run instead of only switching to the scenes
+    #[serde(default, skip_serializing_if = "SwitchActions::is_empty")]
+    pub actions: SwitchActions,
 }
 
 impl SwitchingScenes {
@@ -375,6 +885,8 @@ impl SwitchingScenes {
             normal: normal.into(),
             low: low.into(),
             offline: offline.into(),
+            transitions: SceneTransitions::default(),
+            actions: SwitchActions::default(),
         }
     }
 
@@ -388,6 +900,199 @@ impl SwitchingScenes {
     }
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneTransitions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<Transition>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low: Option<Transition>,
+
+    /// Used when switching back to the previous scene
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Transition>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline: Option<Transition>,
+}
+
+impl SceneTransitions {
+    pub fn is_empty(&self) -> bool {
+        self.normal.is_none()
+            && self.low.is_none()
+            && self.previous.is_none()
+            && self.offline.is_none()
+    }
+
+    pub fn type_to_transition(&self, s_type: &SwitchType) -> Option<&Transition> {
+        match s_type {
+            SwitchType::Normal => self.normal.as_ref(),
+            SwitchType::Low => self.low.as_ref(),
+            SwitchType::Previous => self.previous.as_ref(),
+            SwitchType::Offline => self.offline.as_ref(),
+        }
+    }
+}
+
+/// OBS scene transition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transition {
+    /// Name of the transition in OBS (ex; Cut, Fade)
+    pub name: String,
+
+    /// Duration in milliseconds, the duration set in OBS is used when empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwitchActions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<Vec<SwitchAction>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low: Option<Vec<SwitchAction>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Vec<SwitchAction>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline: Option<Vec<SwitchAction>>,
+}
+
+impl SwitchActions {
+    pub fn is_empty(&self) -> bool {
+        self.normal.is_none()
+            && self.low.is_none()
+            && self.previous.is_none()
+            && self.offline.is_none()
+    }
+
+    /// Actions of the switch type, only switching the scene when none are set
+    pub fn type_to_actions(&self, s_type: &SwitchType) -> &[SwitchAction] {
+        let actions = match s_type {
+            SwitchType::Normal => &self.normal,
+            SwitchType::Low => &self.low,
+            SwitchType::Previous => &self.previous,
+            SwitchType::Offline => &self.offline,
+        };
+
+        actions
+            .as_deref()
+            .unwrap_or(std::slice::from_ref(&SwitchAction::SwitchScene))
+    }
+}
+
+/// Action run on the broadcasting software when switching
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SwitchAction {
+    /// Switch to the scene of the switch type
+    SwitchScene,
+
+    /// Show or hide a scene item in the current scene or its nested scenes
+    #[serde(rename_all = "camelCase")]
+    SceneItem { source: String, enabled: bool },
+
+    /// Enable or disable a filter on a source
+    #[serde(rename_all = "camelCase")]
+    Filter {
+        source: String,
+        filter: String,
+        enabled: bool,
+    },
+
+    /// Mute or unmute an input
+    #[serde(rename_all = "camelCase")]
+    Mute { input: String, muted: bool },
+}
+
+/// OBS text source (GDI+ or FreeType 2) that shows the metrics of the stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BitrateOverlay {
+    /// Name of the text source
+    pub source: String,
+
+    /// Text with the placeholders {bitrate}, {rtt}, {loss} and {server}
+    #[serde(default = "default_overlay_format")]
+    pub format: String,
+
+    /// Text shown when no stream server is online
+    #[serde(default)]
+    pub offline_text: String,
+
+    /// Color of the text as #RRGGBB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    /// Color of the text as #RRGGBB while the bitrate is low
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low_color: Option<String>,
+}
+
+fn default_overlay_format() -> String {
+    "{bitrate} kbps {rtt} ms".to_string()
+}
+
+/// Text and OBS color written to the overlay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayText {
+    pub text: String,
+    pub color: Option<u32>,
+}
+
+impl BitrateOverlay {
+    pub fn render(
+        &self,
+        server: Option<&str>,
+        metrics: Option<&stream_servers::Metrics>,
+        switch_type: SwitchType,
+    ) -> OverlayText {
+        let text = match metrics {
+            Some(metrics) => {
+                let optional = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+
+                self.format
+                    .replace("{bitrate}", &metrics.bitrate.to_string())
+                    .replace("{rtt}", &optional(metrics.rtt.map(|r| format!("{r:.0}"))))
+                    .replace("{loss}", &optional(metrics.loss.map(|l| l.to_string())))
+                    .replace("{server}", server.unwrap_or_default())
+            }
+            None => self.offline_text.to_owned(),
+        };
+
+        let color = if switch_type == SwitchType::Low {
+            self.low_color.as_ref().or(self.color.as_ref())
+        } else {
+            self.color.as_ref()
+        };
+
+        // Go back to white after low when only the low color is set
+        let color = match color {
+            Some(color) => obs_color(color),
+            None if self.low_color.is_some() => Some(0xFFFFFFFF),
+            None => None,
+        };
+
+        OverlayText { text, color }
+    }
+}
+
+/// Converts #RRGGBB to the ABGR color OBS uses
+fn obs_color(color: &str) -> Option<u32> {
+    let rgb = u32::from_str_radix(color.trim_start_matches('#'), 16).ok()?;
+
+    let r = (rgb >> 16) & 0xFF;
+    let g = (rgb >> 8) & 0xFF;
+    let b = rgb & 0xFF;
+
+    Some(0xFF000000 | (b << 16) | (g << 8) | r)
+}
+
 #[derive(Debug)]
 pub enum TriggerType {
     Low,
@@ -410,12 +1115,30 @@ pub struct Triggers {
 
     /// Trigger to switch to the offline scene when RTT is high
     pub rtt_offline: Option<u32>,
+
+    /// Triggers on the format of the stream
+    #[serde(default, skip_serializing_if = "FormatTriggers::is_empty")]
+    pub format: FormatTriggers,
 }
 
 impl Triggers {
     pub fn set_low(&mut self, value: Option<u32>) {
         self.low = value;
     }
+
+    pub fn get_mut(&mut self, kind: &TriggerType) -> &mut Option<u32> {
+        match kind {
+            TriggerType::Low => &mut self.low,
+            TriggerType::Rtt => &mut self.rtt,
+            TriggerType::Offline => &mut self.offline,
+            TriggerType::RttOffline => &mut self.rtt_offline,
+        }
+    }
+
+    /// Whether a trigger on the bitrate is set
+    pub fn uses_bitrate(&self) -> bool {
+        self.low.is_some() || self.offline.is_some()
+    }
 }
 
 impl Default for Triggers {
@@ -425,10 +1148,215 @@ impl Default for Triggers {
             rtt: Some(2500),
             offline: None,
             rtt_offline: None,
+            format: FormatTriggers::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatTriggers {
+    /// Unexpected format when the video height drops below this (ex; 720 )
+    pub min_height: Option<u32>,
+
+    /// Unexpected format when the stream has no audio
+    #[serde(default)]
+    pub require_audio: bool,
+
+    /// Switch to the low scene on an unexpected format instead of only
+    /// warning chat
+    #[serde(default)]
+    pub switch_to_low: bool,
+}
+
+impl FormatTriggers {
+    pub fn is_enabled(&self) -> bool {
+        self.min_height.is_some() || self.require_audio
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the first unexpected format found in the metadata
+    pub fn check(&self, metadata: &stream_servers::StreamMetadata) -> Option<FormatIssue> {
+        if let (Some(min_height), Some(height)) = (self.min_height, metadata.height) {
+            // Some servers report 0 until the first keyframe
+            if height > 0 && height < min_height {
+                return Some(FormatIssue::LowResolution { height, min_height });
+            }
+        }
+
+        if self.require_audio && !metadata.has_audio() {
+            return Some(FormatIssue::NoAudio);
+        }
+
+        None
+    }
+}
+
+/// Media source in OBS that gets refreshed when it's stuck
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceWatchdog {
+    /// Name of the media source receiving the stream
+    pub source: String,
+
+    /// Seconds the source can be stuck before it gets refreshed
+    #[serde(default = "default_watchdog_stuck_seconds")]
+    pub stuck_seconds: u32,
+
+    /// Minimum seconds between two refreshes
+    #[serde(default = "default_watchdog_cooldown_seconds")]
+    pub cooldown_seconds: u64,
+}
+
+fn default_watchdog_stuck_seconds() -> u32 {
+    5
+}
+
+fn default_watchdog_cooldown_seconds() -> u64 {
+    60
+}
+
+#[derive(Debug, Default)]
+pub struct WatchdogState {
+    prev_cursor: Option<i64>,
+    stuck_seconds: u32,
+    last_fix: Option<Instant>,
+
+    // The status of the source couldn't be read last time
+    unreadable: bool,
+}
+
+impl WatchdogState {
+    /// Updates the state with the status of the source, returns true
+    /// when the source should be refreshed
+    pub fn check(&mut self, watchdog: &SourceWatchdog, status: (bool, i64), now: Instant) -> bool {
+        let (playing, cursor) = status;
+        let stuck = !playing || self.prev_cursor == Some(cursor);
+        self.prev_cursor = Some(cursor);
+
+        if !stuck {
+            self.stuck_seconds = 0;
+            return false;
         }
+
+        self.stuck_seconds += 1;
+
+        if self.stuck_seconds < watchdog.stuck_seconds {
+            return false;
+        }
+
+        if let Some(last) = self.last_fix {
+            if now.duration_since(last).as_secs() < watchdog.cooldown_seconds {
+                return false;
+            }
+        }
+
+        self.reset();
+        self.last_fix = Some(now);
+
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.prev_cursor = None;
+        self.stuck_seconds = 0;
+    }
+}
+
+/// OBS input that should have audio while live
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioMonitor {
+    /// Name of the input in OBS
+    pub input: String,
+
+    /// Peak level in dB below which the input counts as silent
+    #[serde(default = "default_audio_threshold_db")]
+    pub threshold_db: f32,
+
+    /// Seconds the input needs to be silent before it's reported
+    #[serde(default = "default_audio_silent_seconds")]
+    pub silent_seconds: u64,
+
+    /// Actions to run when the input goes silent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<SwitchAction>,
+
+    /// Actions to run when the input has audio again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recover_actions: Vec<SwitchAction>,
+}
+
+fn default_audio_threshold_db() -> f32 {
+    -60.0
+}
+
+fn default_audio_silent_seconds() -> u64 {
+    10
+}
+
+#[derive(Debug, Default)]
+pub struct AudioMonitorState {
+    silent_since: Option<Instant>,
+    reported: bool,
+}
+
+impl AudioMonitorState {
+    /// Updates the state with the peak level (as multiplier) of the input,
+    /// returns whether the input is silent when that changed
+    pub fn update(&mut self, monitor: &AudioMonitor, peak: f32, now: Instant) -> Option<bool> {
+        let threshold = 10f32.powf(monitor.threshold_db / 20.0);
+
+        if peak >= threshold {
+            self.silent_since = None;
+
+            if self.reported {
+                self.reported = false;
+                return Some(false);
+            }
+
+            return None;
+        }
+
+        let since = *self.silent_since.get_or_insert(now);
+
+        if self.reported || now.duration_since(since).as_secs() < monitor.silent_seconds {
+            return None;
+        }
+
+        self.reported = true;
+        Some(true)
+    }
+
+    /// Same as [`Self::update`], an input that is missing from the meters
+    /// got removed or renamed and counts as silent
+    pub fn update_input(
+        &mut self,
+        monitor: &AudioMonitor,
+        peak: Option<f32>,
+        now: Instant,
+    ) -> Option<bool> {
+        self.update(monitor, peak.unwrap_or_default(), now)
+    }
+
+    /// Forgets the silence, returns whether it was reported so the actions
+    /// taken for it can be undone
+    pub fn reset(&mut self) -> bool {
+        self.silent_since = None;
+
+        std::mem::take(&mut self.reported)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatIssue {
+    LowResolution { height: u32, min_height: u32 },
+    NoAudio,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SwitchType {
     Normal,
@@ -436,3 +1364,391 @@ pub enum SwitchType {
     Previous,
     Offline,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlay() -> BitrateOverlay {
+        BitrateOverlay {
+            source: "bitrate".to_string(),
+            format: "{server}: {bitrate} kbps {rtt} ms".to_string(),
+            offline_text: "offline".to_string(),
+            color: None,
+            low_color: Some("#ff0000".to_string()),
+        }
+    }
+
+    #[test]
+    fn render_overlay() {
+        let overlay = overlay();
+        let metrics = stream_servers::Metrics {
+            bitrate: 2500,
+            rtt: Some(42.4),
+            loss: None,
+        };
+
+        let text = overlay.render(Some("belabox"), Some(&metrics), SwitchType::Normal);
+        assert_eq!(text.text, "belabox: 2500 kbps 42 ms");
+        assert_eq!(text.color, Some(0xFFFFFFFF));
+
+        let text = overlay.render(Some("belabox"), Some(&metrics), SwitchType::Low);
+        assert_eq!(text.color, Some(0xFF0000FF));
+
+        let metrics = stream_servers::Metrics {
+            bitrate: 300,
+            ..Default::default()
+        };
+        let text = overlay.render(Some("nginx"), Some(&metrics), SwitchType::Low);
+        assert_eq!(text.text, "nginx: 300 kbps - ms");
+
+        let text = overlay.render(None, None, SwitchType::Offline);
+        assert_eq!(text.text, "offline");
+    }
+
+    #[test]
+    fn stuck_source() {
+        let watchdog = SourceWatchdog {
+            source: "camera".to_string(),
+            stuck_seconds: 2,
+            cooldown_seconds: 60,
+        };
+        let mut state = WatchdogState::default();
+        let now = Instant::now();
+
+        assert!(!state.check(&watchdog, (true, 100), now));
+        assert!(!state.check(&watchdog, (true, 1100), now));
+        assert!(!state.check(&watchdog, (true, 1100), now));
+        assert!(state.check(&watchdog, (true, 1100), now));
+
+        // Cooldown
+        assert!(!state.check(&watchdog, (false, 1100), now));
+        assert!(!state.check(&watchdog, (false, 1100), now));
+        assert!(!state.check(&watchdog, (false, 1100), now));
+
+        let later = now + std::time::Duration::from_secs(60);
+        assert!(state.check(&watchdog, (false, 1100), later));
+    }
+
+    #[test]
+    fn audio_silence() {
+        let monitor = AudioMonitor {
+            input: "mic".to_string(),
+            threshold_db: -60.0,
+            silent_seconds: 10,
+            actions: Vec::new(),
+            recover_actions: Vec::new(),
+        };
+        let mut state = AudioMonitorState::default();
+        let now = Instant::now();
+        let secs = std::time::Duration::from_secs;
+
+        assert_eq!(state.update(&monitor, 0.5, now), None);
+        assert_eq!(state.update(&monitor, 0.0001, now), None);
+        assert_eq!(state.update(&monitor, 0.0, now + secs(9)), None);
+        assert_eq!(state.update(&monitor, 0.0, now + secs(10)), Some(true));
+        assert_eq!(state.update(&monitor, 0.0, now + secs(20)), None);
+        assert_eq!(state.update(&monitor, 0.01, now + secs(21)), Some(false));
+        assert_eq!(state.update(&monitor, 0.01, now + secs(22)), None);
+    }
+
+    #[test]
+    fn audio_silence_reset() {
+        let monitor = AudioMonitor {
+            input: "mic".to_string(),
+            threshold_db: -60.0,
+            silent_seconds: 10,
+            actions: Vec::new(),
+            recover_actions: Vec::new(),
+        };
+        let mut state = AudioMonitorState::default();
+        let now = Instant::now();
+        let secs = std::time::Duration::from_secs;
+
+        assert_eq!(state.update(&monitor, 0.0, now), None);
+        assert!(!state.reset());
+        assert_eq!(state.update(&monitor, 0.0, now + secs(5)), None);
+        assert_eq!(state.update(&monitor, 0.0, now + secs(15)), Some(true));
+        assert!(state.reset());
+        assert!(!state.reset());
+        assert_eq!(state.update(&monitor, 0.0, now + secs(16)), None);
+    }
+
+    #[test]
+    fn audio_input_missing() {
+        let monitor = AudioMonitor {
+            input: "mic".to_string(),
+            threshold_db: -60.0,
+            silent_seconds: 10,
+            actions: Vec::new(),
+            recover_actions: Vec::new(),
+        };
+        let mut state = AudioMonitorState::default();
+        let now = Instant::now();
+        let secs = std::time::Duration::from_secs;
+
+        assert_eq!(state.update_input(&monitor, Some(0.5), now), None);
+        assert_eq!(state.update_input(&monitor, None, now + secs(1)), None);
+        assert_eq!(
+            state.update_input(&monitor, None, now + secs(11)),
+            Some(true)
+        );
+
+        // Still gone, no recovery
+        assert_eq!(state.update_input(&monitor, None, now + secs(12)), None);
+        assert_eq!(
+            state.update_input(&monitor, Some(0.5), now + secs(13)),
+            Some(false)
+        );
+    }
+
+    /// Decision of the switcher with the traces played up to `secs`
+    async fn replayed_decision(
+        servers: &mut [stream_servers::StreamServer],
+        traces: &[&str],
+        secs: u64,
+    ) -> (Option<String>, SwitchType) {
+        use stream_servers::{replay::Trace, Replay};
+
+        for (server, text) in servers.iter_mut().zip(traces) {
+            let replay = server.stream_server.as_any_mut().downcast_mut::<Replay>();
+            replay.unwrap().seek(
+                Trace::parse(text).unwrap(),
+                std::time::Duration::from_secs(secs),
+            );
+        }
+
+        let triggers = Triggers::default();
+        let (server, switch_type) = Switcher::get_online_stream_server(servers, &triggers).await;
+
+        (server.map(|s| s.name.to_owned()), switch_type)
+    }
+
+    #[tokio::test]
+    async fn switch_on_replayed_traces() {
+        let server = |name: &str| stream_servers::StreamServer {
+            stream_server: Box::new(stream_servers::Replay {
+                path: String::new(),
+                speed: 1.0,
+                looping: false,
+                player: Default::default(),
+            }),
+            name: name.to_string(),
+            priority: None,
+            override_scenes: None,
+            depends_on: None,
+            enabled: true,
+        };
+        let mut servers = [server("irl"), server("backup")];
+
+        let traces = [
+            "time,bitrate,rtt\n0,6000,40\n10,500,60\n20,,\n30,6000,40\n40,6000,40\n",
+            "time,bitrate\n0,3000\n40,3000\n",
+        ];
+
+        let irl = Some("irl".to_string());
+        let backup = Some("backup".to_string());
+
+        let decision = replayed_decision(&mut servers, &traces, 0).await;
+        assert_eq!(decision, (irl.clone(), SwitchType::Normal));
+
+        let decision = replayed_decision(&mut servers, &traces, 15).await;
+        assert_eq!(decision, (irl.clone(), SwitchType::Low));
+
+        // The irl trace is offline so the backup takes over
+        let decision = replayed_decision(&mut servers, &traces, 25).await;
+        assert_eq!(decision, (backup, SwitchType::Normal));
+
+        let decision = replayed_decision(&mut servers, &traces, 35).await;
+        assert_eq!(decision, (irl, SwitchType::Normal));
+
+        let decision = replayed_decision(&mut servers, &traces, 45).await;
+        assert_eq!(decision, (None, SwitchType::Offline));
+    }
+
+    #[test]
+    fn transitions_per_switch_type() {
+        let scenes: SwitchingScenes = serde_json::from_str(
+            r#"{
+                "normal": "Live",
+                "low": "Low",
+                "offline": "Disconnected",
+                "transitions": {
+                    "normal": { "name": "Fade", "duration": 500 },
+                    "offline": { "name": "Cut" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let normal = scenes.transitions.type_to_transition(&SwitchType::Normal);
+        assert_eq!(
+            normal.map(|t| (t.name.as_str(), t.duration)),
+            Some(("Fade", Some(500)))
+        );
+
+        let offline = scenes.transitions.type_to_transition(&SwitchType::Offline);
+        assert_eq!(
+            offline.map(|t| (t.name.as_str(), t.duration)),
+            Some(("Cut", None))
+        );
+
+        assert!(scenes
+            .transitions
+            .type_to_transition(&SwitchType::Low)
+            .is_none());
+        assert!(scenes
+            .transitions
+            .type_to_transition(&SwitchType::Previous)
+            .is_none());
+
+        // Scenes without transitions keep the old config format
+        let json = serde_json::to_string(&SwitchingScenes::new("Live", "Low", "Disconnected"));
+        assert_eq!(
+            json.unwrap(),
+            r#"{"normal":"Live","low":"Low","offline":"Disconnected"}"#
+        );
+    }
+
+    #[test]
+    fn repeated_switch_type() {
+        let actions = [
+            SwitchAction::SwitchScene,
+            SwitchAction::SceneItem {
+                source: "LOW BITRATE".to_string(),
+                enabled: false,
+            },
+        ];
+
+        let pending = pending_actions(None, &actions, "Live", "Live", SwitchType::Normal);
+        assert_eq!(pending, vec![&actions[1]]);
+
+        let applied = AppliedSwitch {
+            switch_type: SwitchType::Normal,
+            scene: "Live".to_string(),
+            preview_only: false,
+        };
+
+        // Same type again, nothing to do
+        let pending = pending_actions(Some(&applied), &actions, "Live", "Live", SwitchType::Normal);
+        assert!(pending.is_empty());
+
+        // The operator switched away, only switch back
+        let pending = pending_actions(
+            Some(&applied),
+            &actions,
+            "Other",
+            "Live",
+            SwitchType::Normal,
+        );
+        assert_eq!(pending, vec![&actions[0]]);
+
+        let pending = pending_actions(Some(&applied), &actions, "Live", "Low", SwitchType::Low);
+        assert_eq!(pending, vec![&actions[0], &actions[1]]);
+    }
+
+    #[test]
+    fn repeated_preview_suggestion() {
+        let actions = [SwitchAction::SwitchScene];
+        let applied = AppliedSwitch {
+            switch_type: SwitchType::Low,
+            scene: "Low".to_string(),
+            preview_only: true,
+        };
+
+        let pending = pending_actions(Some(&applied), &actions, "Live", "Low", SwitchType::Low);
+        assert!(pending.is_empty());
+
+        let pending = pending_actions(
+            Some(&applied),
+            &actions,
+            "Live",
+            "Offline",
+            SwitchType::Offline,
+        );
+        assert_eq!(pending, vec![&actions[0]]);
+    }
+
+    /// Software that only knows the scenes it got switched to
+    #[derive(Default)]
+    struct SceneRecorder {
+        switched: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl BroadcastingSoftwareLogic for SceneRecorder {
+        async fn switch_scene(&self, scene: &str) -> Result<String, error::Error> {
+            self.switched.lock().unwrap().push(scene.to_string());
+            Ok(scene.to_string())
+        }
+
+        async fn scenes(&self) -> Result<Vec<String>, error::Error> {
+            Ok(self.switched.lock().unwrap().clone())
+        }
+
+        async fn start_streaming(&self) -> Result<(), error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn stop_streaming(&self) -> Result<(), error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn toggle_recording(&self) -> Result<(), error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn is_recording(&self) -> Result<bool, error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn fix(&self, _: Option<&str>) -> Result<Vec<String>, error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn current_scene(&self) -> Result<String, error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn toggle_source(&self, _: &str) -> Result<(String, bool), error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn set_collection_and_profile(
+            &self,
+            _: &config::CollectionPair,
+        ) -> Result<(), error::Error> {
+            Err(error::Error::NotSupported)
+        }
+
+        async fn info(
+            &self,
+            _: &tokio::sync::RwLockReadGuard<crate::state::State>,
+        ) -> Result<crate::state::StreamStatus, error::Error> {
+            Err(error::Error::NotSupported)
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_action_still_switches() {
+        let software = SceneRecorder::default();
+        let item = SwitchAction::SceneItem {
+            source: "Overlay typo".to_string(),
+            enabled: false,
+        };
+        let actions = [&item, &SwitchAction::SwitchScene];
+
+        let success =
+            Switcher::run_actions(&software, &actions, "BRB", SwitchType::Low, None, false).await;
+
+        assert!(!success);
+        assert_eq!(*software.switched.lock().unwrap(), vec!["BRB".to_string()]);
+    }
+
+    #[test]
+    fn convert_color() {
+        assert_eq!(obs_color("#00ff00"), Some(0xFF00FF00));
+        assert_eq!(obs_color("0000ff"), Some(0xFFFF0000));
+        assert_eq!(obs_color("nope"), None);
+    }
+}
@@ -3,9 +3,13 @@ use std::sync::Arc;
 use futures_util::{SinkExt, StreamExt, TryFutureExt};
 use tokio::sync::mpsc::{self, UnboundedSender};
 use tracing::info;
-use warp::{ws::WebSocket, Filter};
+use warp::{http::StatusCode, ws::WebSocket, Filter};
 
-use crate::{user_manager::UserManager, ws};
+use crate::{
+    stream_servers::{self, push::PushError},
+    user_manager::UserManager,
+    ws,
+};
 
 pub struct WebServer {
     port: u16,
@@ -40,7 +44,15 @@ impl WebServer {
                 },
             );
 
-        let routes = ws;
+        // Stats pushed by a sender for a push stream server
+        let push = warp::path!("push" / String)
+            .and(warp::post())
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::content_length_limit(16 * 1024))
+            .and(warp::body::json())
+            .map(Self::push_stats);
+
+        let routes = ws.or(push);
 
         info!("Running web server on 127.0.0.1:{}", self.port);
         let serve = warp::serve(routes).run(([127, 0, 0, 1], self.port));
@@ -49,6 +61,25 @@ impl WebServer {
         tokio::join!(serve, wh);
     }
 
+    fn push_stats(
+        id: String,
+        authorization: Option<String>,
+        metrics: stream_servers::Metrics,
+    ) -> impl warp::Reply {
+        let token = authorization
+            .as_deref()
+            .and_then(|a| a.strip_prefix("Bearer "));
+
+        let status = match stream_servers::push::push(&id, token, metrics) {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(PushError::NotFound) => StatusCode::NOT_FOUND,
+            Err(PushError::Unauthorized) => StatusCode::UNAUTHORIZED,
+            Err(PushError::IdTaken) => StatusCode::CONFLICT,
+        };
+
+        warp::reply::with_status(warp::reply(), status)
+    }
+
     pub async fn user_connected(
         ws: WebSocket,
         websocket_handler: Arc<ws::WsHandler>,
@@ -20,6 +20,14 @@ pub enum Request {
     SetPassword(SetPassword),
     Me,
     Logout,
+    StreamMetadata,
+    SourceInfo,
+    StreamServers,
+    AddStreamServer(AddStreamServer),
+    RemoveStreamServer(StreamServerName),
+    SetStreamServerEnabled(SetStreamServerEnabled),
+    SetStreamServerPriority(SetStreamServerPriority),
+    SetObsConnection(SetObsConnection),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -35,6 +43,42 @@ pub struct SetPassword {
     pub password: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddStreamServer {
+    /// Stream server object in the same format as the config
+    pub server: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamServerName {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetStreamServerEnabled {
+    pub name: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetStreamServerPriority {
+    pub name: String,
+    pub priority: i32,
+}
+
+/// New OBS connection details, no password keeps the current one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetObsConnection {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +142,22 @@ mod tests {
 
         assert_eq!(expected, parsed);
     }
+
+    #[test]
+    fn stream_server_request() {
+        let request = r#"{
+            "type": "setStreamServerPriority",
+            "name": "belabox",
+            "priority": 2
+        }"#;
+
+        let parsed = serde_json::from_str::<Request>(request).unwrap();
+
+        let expected = Request::SetStreamServerPriority(SetStreamServerPriority {
+            name: "belabox".to_string(),
+            priority: 2,
+        });
+
+        assert_eq!(parsed, expected);
+    }
 }
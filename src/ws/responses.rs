@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::config;
+use crate::{config, stream_servers};
 
 /// Message that will be send to a client
 #[derive(Serialize)]
@@ -24,6 +24,10 @@ pub enum Response<'a> {
     Me(Me<'a>),
     UpdatedPassword,
     Logout,
+    StreamMetadata(Vec<ServerMetadata>),
+    SourceInfo(Vec<ServerSourceInfo>),
+    StreamServers(Vec<stream_servers::StreamServerInfo>),
+    UpdatedObsConnection,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,6 +38,10 @@ pub enum ResponseError {
     AuthFailed,
     AuthorizationRequired,
     AlreadyAuthenticated,
+    StreamServerNotFound,
+    StreamServerAlreadyExists,
+    NotSupported,
+    Failed(String),
 }
 
 #[derive(Debug, Serialize)]
@@ -46,6 +54,20 @@ pub struct Me<'a> {
     pub config: Config<'a>,
 }
 
+/// Metadata of the stream on a stream server
+#[derive(Serialize)]
+pub struct ServerMetadata {
+    pub name: String,
+    pub metadata: Option<stream_servers::StreamMetadata>,
+}
+
+/// Source info fields of a stream server
+#[derive(Serialize)]
+pub struct ServerSourceInfo {
+    pub name: String,
+    pub info: Option<stream_servers::SourceInfo>,
+}
+
 /// Config details that will be send in the response
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
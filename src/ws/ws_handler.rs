@@ -6,12 +6,15 @@ use tokio::sync::{
     RwLock,
 };
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use tracing::debug;
+use tracing::{debug, error};
 
-use crate::{user_manager, Noalbs};
+use crate::{error, user_manager, Noalbs};
 
 use super::{
-    requests::{Auth, SetPassword},
+    requests::{
+        AddStreamServer, Auth, SetObsConnection, SetPassword, SetStreamServerEnabled,
+        SetStreamServerPriority, StreamServerName,
+    },
     responses, InternalClientToken, WsClient, WsMessage,
 };
 
@@ -113,6 +116,18 @@ impl WsHandler {
             Request::SetPassword(s) => self.set_password(s, &ws_message).await,
             Request::Me => self.me(&ws_message).await,
             Request::Logout => self.logout(&ws_message).await,
+            Request::StreamMetadata => self.stream_metadata(&ws_message).await,
+            Request::SourceInfo => self.source_info(&ws_message).await,
+            Request::StreamServers => self.stream_servers(&ws_message).await,
+            Request::AddStreamServer(a) => self.add_stream_server(a, &ws_message).await,
+            Request::RemoveStreamServer(r) => self.remove_stream_server(r, &ws_message).await,
+            Request::SetStreamServerEnabled(e) => {
+                self.set_stream_server_enabled(e, &ws_message).await
+            }
+            Request::SetStreamServerPriority(p) => {
+                self.set_stream_server_priority(p, &ws_message).await
+            }
+            Request::SetObsConnection(o) => self.set_obs_connection(o, &ws_message).await,
             Request::Auth(_) => unreachable!(),
         };
     }
@@ -222,6 +237,159 @@ impl WsHandler {
         ws_message.reply(responses::Response::Me(responses::Me { config }));
     }
 
+    async fn stream_metadata(&self, ws_message: &WsMessage) {
+        let lock = self.clients.read().await;
+        let user = lock
+            .get(&ws_message.internal_token)
+            .unwrap()
+            .user
+            .as_ref()
+            .unwrap();
+
+        let state = user.state.read().await;
+        let mut servers = Vec::new();
+
+        for server in &state.config.switcher.stream_servers {
+            servers.push(responses::ServerMetadata {
+                name: server.name.to_owned(),
+                metadata: server.stream_server.metadata().await,
+            });
+        }
+
+        ws_message.reply(responses::Response::StreamMetadata(servers));
+    }
+
+    async fn source_info(&self, ws_message: &WsMessage) {
+        let user = self.get_user(ws_message).await;
+        let state = user.state.read().await;
+        let mut servers = Vec::new();
+
+        for server in &state.config.switcher.stream_servers {
+            servers.push(responses::ServerSourceInfo {
+                name: server.name.to_owned(),
+                info: server.stream_server.source_info().await,
+            });
+        }
+
+        ws_message.reply(responses::Response::SourceInfo(servers));
+    }
+
+    async fn get_user(&self, ws_message: &WsMessage) -> Arc<Noalbs> {
+        let lock = self.clients.read().await;
+
+        lock.get(&ws_message.internal_token)
+            .unwrap()
+            .user
+            .to_owned()
+            .unwrap()
+    }
+
+    async fn stream_servers(&self, ws_message: &WsMessage) {
+        let user = self.get_user(ws_message).await;
+
+        ws_message.reply(responses::Response::StreamServers(
+            user.get_stream_servers().await,
+        ));
+    }
+
+    async fn add_stream_server(&self, add: &AddStreamServer, ws_message: &WsMessage) {
+        let server = match serde_json::from_value(add.server.to_owned()) {
+            Ok(s) => s,
+            Err(e) => {
+                ws_message.reply(responses::Response::Error(
+                    responses::ResponseError::Deserialize(Some(e.to_string())),
+                ));
+                return;
+            }
+        };
+
+        let user = self.get_user(ws_message).await;
+        let result = user.add_stream_server(server).await;
+        self.reply_stream_servers(user, result, ws_message).await;
+    }
+
+    async fn remove_stream_server(&self, remove: &StreamServerName, ws_message: &WsMessage) {
+        let user = self.get_user(ws_message).await;
+        let result = user.remove_stream_server(&remove.name).await;
+        self.reply_stream_servers(user, result, ws_message).await;
+    }
+
+    async fn set_stream_server_enabled(
+        &self,
+        enabled: &SetStreamServerEnabled,
+        ws_message: &WsMessage,
+    ) {
+        let user = self.get_user(ws_message).await;
+        let result = user
+            .set_stream_server_enabled(&enabled.name, enabled.enabled)
+            .await;
+        self.reply_stream_servers(user, result, ws_message).await;
+    }
+
+    async fn set_stream_server_priority(
+        &self,
+        priority: &SetStreamServerPriority,
+        ws_message: &WsMessage,
+    ) {
+        let user = self.get_user(ws_message).await;
+        let result = user
+            .set_stream_server_priority(&priority.name, priority.priority)
+            .await;
+        self.reply_stream_servers(user, result, ws_message).await;
+    }
+
+    async fn set_obs_connection(&self, obs: &SetObsConnection, ws_message: &WsMessage) {
+        let user = self.get_user(ws_message).await;
+        let result = user
+            .set_obs_connection(obs.host.to_owned(), obs.port, obs.password.to_owned())
+            .await;
+
+        let error = match result {
+            Ok(()) => {
+                if let Err(e) = user.save_config().await {
+                    error!("Error saving config: {}", e);
+                }
+
+                ws_message.reply(responses::Response::UpdatedObsConnection);
+                return;
+            }
+            Err(error::Error::NotSupported) => responses::ResponseError::NotSupported,
+            Err(e) => responses::ResponseError::Failed(e.to_string()),
+        };
+
+        ws_message.reply(responses::Response::Error(error));
+    }
+
+    /// Saves the config and replies with the stream servers after a change
+    async fn reply_stream_servers(
+        &self,
+        user: Arc<Noalbs>,
+        result: Result<(), error::Error>,
+        ws_message: &WsMessage,
+    ) {
+        let error = match result {
+            Ok(()) => {
+                if let Err(e) = user.save_config().await {
+                    error!("Error saving config: {}", e);
+                }
+
+                ws_message.reply(responses::Response::StreamServers(
+                    user.get_stream_servers().await,
+                ));
+                return;
+            }
+            Err(error::Error::StreamServerAlreadyExists) => {
+                responses::ResponseError::StreamServerAlreadyExists
+            }
+            Err(e @ (error::Error::PushIdTaken | error::Error::BitrateTriggersWithObs)) => {
+                responses::ResponseError::Failed(e.to_string())
+            }
+            Err(_) => responses::ResponseError::StreamServerNotFound,
+        };
+
+        ws_message.reply(responses::Response::Error(error));
+    }
+
     async fn logout(&self, ws_message: &WsMessage) {
         let mut lock = self.clients.write().await;
         let client = lock.get_mut(&ws_message.internal_token).unwrap();